@@ -0,0 +1,220 @@
+// Shared leaderboard: keeps every submitted run in one JSON file and serves it over HTTP.
+// See src/leaderboard.rs for the endpoints.
+//
+//   snake-server [--addr 127.0.0.1:7878] [--data leaderboard.json]
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use snake_shared::leaderboard::{RankedScore, Submission, SubmitResponse, DEFAULT_LIMIT, MAX_NAME_LEN};
+use snake_shared::replay::Replay;
+use snake_shared::scores::{Difficulty, GameMode, ScoreEntry};
+use snake_shared::storage;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+const DEFAULT_DATA_FILE: &str = "leaderboard.json";
+// A replay is a few bytes per turn, so this leaves plenty of room for very long runs
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct StoredScore {
+    id: u64,
+    entry: ScoreEntry,
+    replay: Option<Replay>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Board {
+    next_id: u64,
+    scores: Vec<StoredScore>,
+}
+
+impl Board {
+    // A missing file starts an empty board; an unreadable one stops the server rather than being overwritten
+    fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| format!("Couldn't load {}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Board::default()),
+            Err(e) => Err(format!("Couldn't read {}: {}", path.display(), e)),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        storage::write_atomic(path, &json)
+    }
+
+    // Best first; ties go to whoever got there first
+    fn table(&self, difficulty: Difficulty, mode: GameMode, custom_key: Option<u64>) -> Vec<&StoredScore> {
+        let mut table: Vec<&StoredScore> = self.scores.iter()
+            .filter(|stored| stored.entry.in_table(difficulty, mode, custom_key))
+            .collect();
+        table.sort_by_key(|stored| (std::cmp::Reverse(stored.entry.score), stored.id));
+        table
+    }
+}
+
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+// Query values are plain enum names and numbers, so no percent-decoding is needed
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+fn parse_enum<T: serde::de::DeserializeOwned>(name: &str, value: &str) -> Result<T, ApiError> {
+    serde_json::from_value(Value::String(value.to_string()))
+        .map_err(|_| ApiError::new(400, format!("unknown {} '{}'", name, value)))
+}
+
+fn query_scores(board: &Board, query: &str) -> Result<(u16, String), ApiError> {
+    let difficulty = query_param(query, "difficulty").ok_or_else(|| ApiError::new(400, "missing difficulty"))?;
+    let difficulty: Difficulty = parse_enum("difficulty", difficulty)?;
+    let mode = match query_param(query, "mode") {
+        Some(mode) => parse_enum("mode", mode)?,
+        None => GameMode::default(),
+    };
+    let limit = match query_param(query, "limit") {
+        Some(limit) => limit.parse().map_err(|_| ApiError::new(400, "limit must be a number"))?,
+        None => DEFAULT_LIMIT,
+    };
+    let custom_key = match query_param(query, "custom") {
+        Some(key) => Some(key.parse().map_err(|_| ApiError::new(400, "custom must be a number"))?),
+        None => None,
+    };
+
+    let ranked: Vec<RankedScore> = board.table(difficulty, mode, custom_key)
+        .into_iter()
+        .take(limit)
+        .enumerate()
+        .map(|(i, stored)| RankedScore {
+            rank: i + 1,
+            id: stored.id,
+            has_replay: stored.replay.is_some(),
+            entry: stored.entry.clone(),
+        })
+        .collect();
+    Ok((200, serde_json::to_string(&ranked).unwrap_or_default()))
+}
+
+fn submit_score(board: &mut Board, data_path: &Path, request: &mut Request) -> Result<(u16, String), ApiError> {
+    let mut body = String::new();
+    request.as_reader()
+        .take(MAX_BODY_BYTES)
+        .read_to_string(&mut body)
+        .map_err(|e| ApiError::new(400, e.to_string()))?;
+    let mut submission: Submission = serde_json::from_str(&body).map_err(|e| ApiError::new(400, e.to_string()))?;
+
+    let name = submission.entry.player_name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(ApiError::new(400, format!("player name must be 1 to {} characters", MAX_NAME_LEN)));
+    }
+    submission.entry.player_name = name.to_string();
+    if let Some(replay) = &submission.replay {
+        if replay.difficulty != submission.entry.difficulty || replay.mode != submission.entry.mode {
+            return Err(ApiError::new(400, "replay doesn't match the submitted difficulty and mode"));
+        }
+        if replay.custom.map(|custom| custom.key()) != submission.entry.custom_key {
+            return Err(ApiError::new(400, "replay doesn't match the submitted custom difficulty"));
+        }
+    }
+
+    let id = board.next_id;
+    let (difficulty, mode, custom_key) = (submission.entry.difficulty, submission.entry.mode, submission.entry.custom_key);
+    board.scores.push(StoredScore {
+        id,
+        entry: submission.entry,
+        replay: submission.replay,
+    });
+    board.next_id += 1;
+    if let Err(e) = board.save(data_path) {
+        board.scores.pop();
+        board.next_id -= 1;
+        eprintln!("Failed to save {}: {}", data_path.display(), e);
+        return Err(ApiError::new(500, "couldn't save the score"));
+    }
+
+    let rank = board.table(difficulty, mode, custom_key)
+        .iter()
+        .position(|stored| stored.id == id)
+        .map_or(0, |index| index + 1);
+    let response = SubmitResponse { id, rank };
+    Ok((201, serde_json::to_string(&response).unwrap_or_default()))
+}
+
+fn get_replay(board: &Board, id: &str) -> Result<(u16, String), ApiError> {
+    let id: u64 = id.parse().map_err(|_| ApiError::new(404, "no such score"))?;
+    let stored = board.scores.iter()
+        .find(|stored| stored.id == id)
+        .ok_or_else(|| ApiError::new(404, "no such score"))?;
+    let replay = stored.replay.as_ref().ok_or_else(|| ApiError::new(404, "no replay for this score"))?;
+    Ok((200, serde_json::to_string(replay).unwrap_or_default()))
+}
+
+fn route(board: &mut Board, data_path: &Path, request: &mut Request) -> Result<(u16, String), ApiError> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    match (request.method(), path) {
+        (Method::Get, "/scores") => query_scores(board, query),
+        (Method::Post, "/scores") => submit_score(board, data_path, request),
+        (Method::Get, path) if path.starts_with("/replays/") => get_replay(board, &path["/replays/".len()..]),
+        (_, "/scores") => Err(ApiError::new(405, "use GET or POST")),
+        _ => Err(ApiError::new(404, "not found")),
+    }
+}
+
+fn main() {
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut data_path = PathBuf::from(DEFAULT_DATA_FILE);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--addr", Some(value)) => addr = value,
+            ("--data", Some(value)) => data_path = PathBuf::from(value),
+            _ => {
+                eprintln!("Unexpected argument: {}\nUsage: snake-server [--addr <host:port>] [--data <file>]", arg);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut board = Board::load(&data_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let server = Server::http(&addr).unwrap_or_else(|e| {
+        eprintln!("Couldn't listen on {}: {}", addr, e);
+        std::process::exit(1);
+    });
+    println!("Serving {} scores from {} on http://{}", board.scores.len(), data_path.display(), addr);
+
+    let json_header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    for mut request in server.incoming_requests() {
+        let (status, body) = match route(&mut board, &data_path, &mut request) {
+            Ok(reply) => reply,
+            Err(e) => (e.status, serde_json::json!({ "error": e.message }).to_string()),
+        };
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(json_header.clone());
+        if let Err(e) = request.respond(response) {
+            eprintln!("Failed to send response: {}", e);
+        }
+    }
+}
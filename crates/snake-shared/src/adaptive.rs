@@ -0,0 +1,88 @@
+// Adaptive difficulty: a level that rises while the player is eating quickly and falls while
+// they're struggling, driving the speed, hazards and score multiplier in place of a fixed difficulty
+use serde::{Deserialize, Serialize};
+use crate::scores::DifficultyInfo;
+
+pub const MAX_LEVEL: f32 = 10.0;
+// Where a player with no recent scores starts
+pub const DEFAULT_LEVEL: f32 = 3.0;
+// Average recent score that earns each starting level
+const POINTS_PER_LEVEL: f32 = 60.0;
+// Seconds between eats that leaves the level where it is
+const TARGET_EAT_TIME: f32 = 6.0;
+const NEAR_MISS_PENALTY: f32 = 0.3;
+// Level lost per second once the player has gone twice the target time without eating
+const DROUGHT_DECAY: f32 = 0.1;
+// Seconds between moves at level 0 and at the top level
+const SLOWEST_COOLDOWN: f32 = 0.2;
+const FASTEST_COOLDOWN: f32 = 0.05;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Adaptive {
+    pub level: f32,
+    since_eat: f32,
+    // Close calls since the last eat
+    near_misses: u32,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Adaptive::new(DEFAULT_LEVEL)
+    }
+}
+
+impl Adaptive {
+    pub fn new(level: f32) -> Self {
+        Adaptive {
+            level: level.clamp(0.0, MAX_LEVEL),
+            since_eat: 0.0,
+            near_misses: 0,
+        }
+    }
+
+    // Starts from the player's recent scores, most recent last
+    pub fn from_recent(scores: &[u32]) -> Self {
+        if scores.is_empty() {
+            return Adaptive::default();
+        }
+        let average = scores.iter().sum::<u32>() as f32 / scores.len() as f32;
+        Adaptive::new(average / POINTS_PER_LEVEL)
+    }
+
+    // Returns true if the level changed
+    pub fn tick(&mut self, dt: f32) -> bool {
+        self.since_eat += dt;
+        if self.since_eat < TARGET_EAT_TIME * 2.0 || self.level <= 0.0 {
+            return false;
+        }
+        self.level = (self.level - DROUGHT_DECAY * dt).max(0.0);
+        true
+    }
+
+    pub fn near_miss(&mut self) {
+        self.near_misses += 1;
+    }
+
+    pub fn on_eat(&mut self) {
+        let pace = (1.0 - self.since_eat / TARGET_EAT_TIME).clamp(-1.0, 1.0) * 0.5;
+        self.level = (self.level + pace - self.near_misses as f32 * NEAR_MISS_PENALTY).clamp(0.0, MAX_LEVEL);
+        self.since_eat = 0.0;
+        self.near_misses = 0;
+    }
+
+    pub fn cooldown(&self) -> f32 {
+        SLOWEST_COOLDOWN - (SLOWEST_COOLDOWN - FASTEST_COOLDOWN) * self.level / MAX_LEVEL
+    }
+
+    // The level stands in for a difficulty: no fixed speed-up, since the level sets the pace
+    pub fn info(&self) -> DifficultyInfo {
+        DifficultyInfo {
+            speed: self.cooldown(),
+            score_multiplier: 1.0 + self.level * 0.2,
+            hazard_chance: (self.level / MAX_LEVEL * 0.4) as f64,
+            acceleration: 0.0,
+            min_cooldown: self.cooldown(),
+            growth: 1,
+        }
+    }
+}
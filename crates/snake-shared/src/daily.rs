@@ -0,0 +1,70 @@
+// Daily Challenge: the seed (and so the board) comes from the calendar date, so everyone
+// playing on the same day gets the same walls and food sequence
+use crate::scores::{Difficulty, ScoreEntry};
+use crate::storage;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Same speed for everyone, so the daily board only compares skill
+pub const DAILY_DIFFICULTY: Difficulty = Difficulty::Medium;
+
+// Spreads consecutive day numbers into unrelated seeds (splitmix64 finalizer)
+pub fn seed_for(date: NaiveDate) -> u64 {
+    let mut z = (date.num_days_from_ce() as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DailyEntry {
+    // The day the run was started, which is what it counts for even if it ends after midnight
+    pub date: NaiveDate,
+    pub entry: ScoreEntry,
+}
+
+// Official attempts only: each player's first finished run of the day
+#[derive(Serialize, Deserialize, Default)]
+pub struct DailyBoard {
+    pub entries: Vec<DailyEntry>,
+}
+
+impl DailyBoard {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        storage::load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        storage::write_atomic(path, &json)
+    }
+
+    pub fn played(&self, date: NaiveDate, player_name: &str) -> bool {
+        self.entries.iter().any(|daily| daily.date == date && daily.entry.player_name == player_name)
+    }
+
+    // Best first
+    pub fn day(&self, date: NaiveDate) -> Vec<&ScoreEntry> {
+        let mut day: Vec<&ScoreEntry> = self.entries.iter()
+            .filter(|daily| daily.date == date)
+            .map(|daily| &daily.entry)
+            .collect();
+        day.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        day
+    }
+
+    // Where a score would land on that day's board
+    pub fn rank(&self, date: NaiveDate, score: u32) -> usize {
+        self.day(date).iter().filter(|entry| entry.score >= score).count() + 1
+    }
+
+    // Returns false if the player already has an official attempt that day
+    pub fn record(&mut self, date: NaiveDate, entry: ScoreEntry) -> bool {
+        if self.played(date, &entry.player_name) {
+            return false;
+        }
+        self.entries.push(DailyEntry { date, entry });
+        true
+    }
+}
@@ -0,0 +1,55 @@
+// Wire format for the shared leaderboard served by `snake-server`, and the game's client for it
+// (behind the `client` feature, so the server doesn't pull in an HTTP client)
+//
+//   POST /scores                                  Submission -> SubmitResponse
+//   GET  /scores?difficulty=&mode=&limit=&custom= -> [RankedScore], best first
+//   GET  /replays/<id>                            -> Replay
+use crate::replay::Replay;
+use crate::scores::ScoreEntry;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use std::time::Duration;
+
+pub const DEFAULT_LIMIT: usize = 10;
+pub const MAX_NAME_LEN: usize = 16;
+#[cfg(feature = "client")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Submission {
+    pub entry: ScoreEntry,
+    #[serde(default)]
+    pub replay: Option<Replay>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubmitResponse {
+    pub id: u64,
+    // Position among all submitted scores with the same difficulty and mode
+    pub rank: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RankedScore {
+    pub rank: usize,
+    pub id: u64,
+    pub has_replay: bool,
+    #[serde(flatten)]
+    pub entry: ScoreEntry,
+}
+
+#[cfg(feature = "client")]
+fn endpoint(server: &str, path: &str) -> String {
+    format!("{}{}", server.trim_end_matches('/'), path)
+}
+
+// Blocks for up to REQUEST_TIMEOUT, so the game calls it off the main thread
+#[cfg(feature = "client")]
+pub fn submit(server: &str, submission: &Submission) -> Result<SubmitResponse, String> {
+    ureq::post(&endpoint(server, "/scores"))
+        .timeout(REQUEST_TIMEOUT)
+        .send_json(submission)
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,7 @@
+// Score data and storage shared by the game and the `snake-server` leaderboard
+pub mod adaptive;
+pub mod daily;
+pub mod leaderboard;
+pub mod replay;
+pub mod scores;
+pub mod storage;
@@ -0,0 +1,106 @@
+// Enough of a run to play it back: the food RNG seed, the settings that shape the board, and every
+// turn and the tick it happened on. Everything that comes and goes on the board is timed in ticks
+// rather than seconds, and the things that still run on the clock (the Survival arena's shrinks and
+// the Adaptive level) are recorded by tick, so the recording doesn't depend on how fast the game ran.
+use crate::scores::{CustomDifficulty, Difficulty, GameMode};
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ReplayTurn {
+    pub tick: u32,
+    pub direction: Direction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ReplayLevel {
+    pub tick: u32,
+    pub level: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub mode: GameMode,
+    // Movement steps taken, including the one the run ended on
+    pub ticks: u32,
+    pub turns: Vec<ReplayTurn>,
+    // Settings a Custom difficulty run was played with
+    #[serde(default)]
+    pub custom: Option<CustomDifficulty>,
+    // Regular and big food kept on the board, which decides when new food is rolled
+    #[serde(default = "default_food_count")]
+    pub food_count: usize,
+    // Random portals that move every few foods; the daily board's fixed pair comes with the mode
+    #[serde(default)]
+    pub portals: bool,
+    // Patrolling blocks placed at the start
+    #[serde(default)]
+    pub moving_blocks: bool,
+    // Ticks taken when each ring of the Survival arena closed, since that runs on the clock
+    #[serde(default)]
+    pub shrinks: Vec<u32>,
+    // Adaptive level in effect from each tick on, which sets the hazard chance and score multiplier;
+    // only the ticks where it changed are kept
+    #[serde(default)]
+    pub levels: Vec<ReplayLevel>,
+}
+
+fn default_food_count() -> usize {
+    1
+}
+
+impl Replay {
+    pub fn new(seed: u64, difficulty: Difficulty, mode: GameMode) -> Self {
+        Replay {
+            seed,
+            difficulty,
+            mode,
+            ticks: 0,
+            turns: Vec::new(),
+            custom: None,
+            food_count: default_food_count(),
+            portals: false,
+            moving_blocks: false,
+            shrinks: Vec::new(),
+            levels: Vec::new(),
+        }
+    }
+
+    // Called before `record_tick` on each movement step of an Adaptive run
+    pub fn record_level(&mut self, level: f32) {
+        if self.levels.last().is_none_or(|last| last.level != level) {
+            self.levels.push(ReplayLevel { tick: self.ticks, level });
+        }
+    }
+
+    // Called once per movement step with the turn taken on it, if any
+    pub fn record_tick(&mut self, turn: Option<Direction>) {
+        if let Some(direction) = turn {
+            self.turns.push(ReplayTurn {
+                tick: self.ticks,
+                direction,
+            });
+        }
+        self.ticks += 1;
+    }
+}
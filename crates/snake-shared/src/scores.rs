@@ -0,0 +1,255 @@
+// Score records and the difficulty/mode tables they're kept in
+use crate::adaptive::Adaptive;
+use chrono::{DateTime, Datelike, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Kept deep enough that the today/this week views still have something to show
+pub const MAX_SCORES_PER_DIFFICULTY: usize = 50;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+    // Tuned by the player; see `CustomDifficulty`
+    Custom,
+    // Follows how the player is doing; see `Adaptive`
+    Adaptive,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct DifficultyInfo {
+    pub speed: f32,
+    pub score_multiplier: f32,
+    // Chance of a hazard appearing each time food is eaten
+    pub hazard_chance: f64,
+    // Fraction the delay between moves shrinks by per food, and the shortest it gets
+    pub acceleration: f32,
+    pub min_cooldown: f32,
+    // Extra segments per segment a food normally adds
+    pub growth: usize,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 6] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+        Difficulty::Custom,
+        Difficulty::Adaptive,
+    ];
+
+    pub fn get_info(&self) -> DifficultyInfo {
+        match self {
+            Difficulty::Easy => DifficultyInfo {
+                speed: 0.2,
+                score_multiplier: 1.0,
+                hazard_chance: 0.0,
+                acceleration: 0.05,
+                min_cooldown: 0.05,
+                growth: 1,
+            },
+            Difficulty::Medium => DifficultyInfo {
+                speed: 0.15,
+                score_multiplier: 1.5,
+                hazard_chance: 0.1,
+                acceleration: 0.05,
+                min_cooldown: 0.05,
+                growth: 1,
+            },
+            Difficulty::Hard => DifficultyInfo {
+                speed: 0.1,
+                score_multiplier: 2.0,
+                hazard_chance: 0.25,
+                acceleration: 0.05,
+                min_cooldown: 0.05,
+                growth: 1,
+            },
+            Difficulty::Expert => DifficultyInfo {
+                speed: 0.07,
+                score_multiplier: 3.0,
+                hazard_chance: 0.4,
+                acceleration: 0.05,
+                min_cooldown: 0.05,
+                growth: 1,
+            },
+            // Custom runs use the player's own settings; these are just the defaults
+            Difficulty::Custom => CustomDifficulty::default().info(),
+            // Adaptive runs change as they go; this is where a new player starts
+            Difficulty::Adaptive => Adaptive::default().info(),
+        }
+    }
+}
+
+// Parameters of the Custom difficulty, edited in the game and saved with its settings
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomDifficulty {
+    // Seconds between moves at the start of a run
+    pub start_cooldown: f32,
+    pub acceleration: f32,
+    pub min_cooldown: f32,
+    pub growth: usize,
+    pub score_multiplier: f32,
+}
+
+impl Default for CustomDifficulty {
+    fn default() -> Self {
+        CustomDifficulty {
+            start_cooldown: 0.15,
+            acceleration: 0.05,
+            min_cooldown: 0.05,
+            growth: 1,
+            score_multiplier: 1.0,
+        }
+    }
+}
+
+impl CustomDifficulty {
+    pub fn info(&self) -> DifficultyInfo {
+        DifficultyInfo {
+            speed: self.start_cooldown,
+            score_multiplier: self.score_multiplier,
+            hazard_chance: 0.1,
+            acceleration: self.acceleration,
+            min_cooldown: self.min_cooldown,
+            growth: self.growth,
+        }
+    }
+
+    // Identifies the high score table for these settings; FNV-1a so it's the same on every build
+    pub fn key(&self) -> u64 {
+        let words = [
+            self.start_cooldown.to_bits(),
+            self.acceleration.to_bits(),
+            self.min_cooldown.to_bits(),
+            self.growth as u32,
+            self.score_multiplier.to_bits(),
+        ];
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in words.iter().flat_map(|word| word.to_le_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100_0000_01b3);
+        }
+        hash
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Daily,
+    TimeAttack,
+    Survival,
+    // Practice without dying; never recorded
+    Zen,
+}
+
+impl GameMode {
+    // Modes with tables in the high score file; daily runs go on their own board
+    pub const ALL: [GameMode; 3] = [GameMode::Classic, GameMode::TimeAttack, GameMode::Survival];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Daily => "Daily",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Survival => "Survival",
+            GameMode::Zen => "Zen",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScoreEntry {
+    pub player_name: String,
+    pub score: u32,
+    pub difficulty: Difficulty,
+    // Older score files predate modes and are all classic runs
+    #[serde(default)]
+    pub mode: GameMode,
+    pub timestamp: DateTime<Local>,
+    // Which Custom difficulty table the score belongs to; `None` for the standard difficulties
+    #[serde(default)]
+    pub custom_key: Option<u64>,
+    // Level an Adaptive run finished on
+    #[serde(default)]
+    pub adaptive_level: Option<f32>,
+}
+
+impl ScoreEntry {
+    pub fn in_table(&self, difficulty: Difficulty, mode: GameMode, custom_key: Option<u64>) -> bool {
+        self.difficulty == difficulty && self.mode == mode && self.custom_key == custom_key
+    }
+}
+
+// Sorts best-first and keeps only the top scores per difficulty and mode, and per settings for Custom
+pub fn trim_high_scores(scores: &mut Vec<ScoreEntry>) {
+    scores.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+
+    let mut filtered_scores = Vec::new();
+    for diff in Difficulty::ALL {
+        for mode in GameMode::ALL {
+            let mut counts: HashMap<Option<u64>, usize> = HashMap::new();
+            for score in scores.iter() {
+                if score.difficulty != diff || score.mode != mode {
+                    continue;
+                }
+                let count = counts.entry(score.custom_key).or_insert(0);
+                if *count < MAX_SCORES_PER_DIFFICULTY {
+                    filtered_scores.push(score.clone());
+                    *count += 1;
+                }
+            }
+        }
+    }
+    *scores = filtered_scores;
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum Period {
+    Today,
+    // Since Monday
+    ThisWeek,
+    #[default]
+    AllTime,
+}
+
+impl Period {
+    pub const ALL: [Period; 3] = [Period::Today, Period::ThisWeek, Period::AllTime];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Period::Today => "Today",
+            Period::ThisWeek => "This week",
+            Period::AllTime => "All time",
+        }
+    }
+
+    pub fn contains(&self, timestamp: &DateTime<Local>, now: &DateTime<Local>) -> bool {
+        let (day, today) = (timestamp.date_naive(), now.date_naive());
+        match self {
+            Period::Today => day == today,
+            Period::ThisWeek => {
+                let monday = today - chrono::Days::new(today.weekday().num_days_from_monday() as u64);
+                day >= monday && day <= today
+            }
+            Period::AllTime => true,
+        }
+    }
+}
+
+// Each player's single best entry, best first; expects `scores` already sorted best-first
+pub fn personal_bests<'a>(scores: impl Iterator<Item = &'a ScoreEntry>) -> Vec<&'a ScoreEntry> {
+    let mut bests: Vec<&ScoreEntry> = Vec::new();
+    for entry in scores {
+        if !bests.iter().any(|best| best.player_name == entry.player_name) {
+            bests.push(entry);
+        }
+    }
+    bests
+}
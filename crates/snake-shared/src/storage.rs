@@ -0,0 +1,305 @@
+// On-disk formats and crash-safe writes for the game's save files
+use crate::scores::ScoreEntry;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const HIGH_SCORES_FILE: &str = "high_scores.json";
+pub const SETTINGS_FILE: &str = "settings.json";
+pub const PROFILES_FILE: &str = "profiles.json";
+pub const SAVEGAME_FILE: &str = "savegame.json";
+pub const DAILY_SCORES_FILE: &str = "daily_scores.json";
+const DATA_DIR_ENV: &str = "SNAKE_DATA_DIR";
+// Bump this and add a step to `migrate_high_scores` whenever the layout changes
+const HIGH_SCORES_VERSION: u64 = 2;
+
+#[derive(Serialize)]
+struct HighScoreFileOut<'a> {
+    version: u64,
+    scores: &'a [ScoreEntry],
+}
+
+#[derive(Deserialize)]
+struct HighScoreFile {
+    scores: Vec<ScoreEntry>,
+}
+
+// Where scores, settings and saves live: the --data-dir flag, then $SNAKE_DATA_DIR,
+// then the platform data directory (e.g. ~/.local/share/snake_game on Linux)
+pub fn data_dir(cli_override: Option<PathBuf>) -> io::Result<PathBuf> {
+    let dir = cli_override
+        .or_else(|| std::env::var_os(DATA_DIR_ENV).map(PathBuf::from))
+        .or_else(|| directories::ProjectDirs::from("", "", "snake_game").map(|dirs| dirs.data_dir().to_path_buf()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory to store game data in"))?;
+    fs::create_dir_all(&dir)?;
+    adopt_legacy_files(&dir);
+    Ok(dir)
+}
+
+// Older builds kept everything in the working directory; bring those files along once
+fn adopt_legacy_files(dir: &Path) {
+    for name in [HIGH_SCORES_FILE, SETTINGS_FILE, PROFILES_FILE, SAVEGAME_FILE] {
+        let legacy = Path::new(name);
+        let target = dir.join(name);
+        if legacy.is_file() && !target.exists() {
+            if let Err(e) = fs::copy(legacy, &target) {
+                eprintln!("Failed to copy {} into {}: {}", name, dir.display(), e);
+            }
+        }
+    }
+}
+
+// Sounds ship next to the binary; fall back to the source tree for `cargo run`
+pub fn resource_dir() -> PathBuf {
+    let beside_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("resources")));
+    let in_manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(|dir| PathBuf::from(dir).join("resources"));
+    beside_exe
+        .into_iter()
+        .chain(in_manifest_dir)
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from("resources"))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Writes to a temp file first and renames it into place, so a crash mid-write
+// leaves either the old file or the new one, never a truncated mix
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+fn migrate_high_scores(version: u64, value: Value) -> Value {
+    match version {
+        // v1 was a bare array of entries, from before the file carried a version
+        1 => json!({ "version": 2, "scores": value }),
+        _ => value,
+    }
+}
+
+fn parse_high_scores(contents: &str) -> Result<Vec<ScoreEntry>, String> {
+    let mut value: Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    let mut version = match &value {
+        Value::Array(_) => 1,
+        Value::Object(fields) => fields
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or("missing schema version")?,
+        _ => return Err("expected a list of scores".to_string()),
+    };
+    if version > HIGH_SCORES_VERSION {
+        return Err(format!("saved by a newer version of the game (schema v{})", version));
+    }
+
+    while version < HIGH_SCORES_VERSION {
+        value = migrate_high_scores(version, value);
+        version += 1;
+    }
+    let file: HighScoreFile = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    Ok(file.scores)
+}
+
+// A missing file is just an empty table. A file that can't be parsed is copied aside
+// untouched and the last good .bak is loaded instead; the notice that comes back with
+// the scores says so. Only when there's no usable backup either is it an error.
+pub fn load_high_scores(path: &Path) -> Result<(Vec<ScoreEntry>, Option<String>), String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), None)),
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    };
+    let error = match parse_high_scores(&contents) {
+        Ok(scores) => return Ok((scores, None)),
+        Err(e) => keep_corrupt(path, e),
+    };
+
+    let backup = with_suffix(path, ".bak");
+    match fs::read_to_string(&backup).map_err(|e| e.to_string()).and_then(|contents| parse_high_scores(&contents)) {
+        Ok(scores) => Ok((scores, Some(format!("{} The scores were restored from {}.", error, backup.display())))),
+        Err(_) => Err(error),
+    }
+}
+
+fn parses_as_high_scores(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|contents| parse_high_scores(&contents).is_ok())
+}
+
+// Copies an unreadable file aside so the next save can't destroy it, and says so
+fn keep_corrupt(path: &Path, error: impl Display) -> String {
+    let kept = with_suffix(path, ".corrupt");
+    match fs::copy(path, &kept) {
+        Ok(_) => format!("Couldn't load {} ({}). A copy was kept as {}.", path.display(), error, kept.display()),
+        Err(_) => format!("Couldn't load {} ({}).", path.display(), error),
+    }
+}
+
+// The other data files get the same treatment as the high scores: missing means empty,
+// unreadable is kept aside and reported
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&contents).map_err(|e| keep_corrupt(path, e))
+}
+
+// Keeps the previous file as a .bak before replacing it, unless it couldn't be loaded:
+// then the .bak is still the last good table and mustn't be overwritten
+pub fn save_high_scores(path: &Path, scores: &[ScoreEntry]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(&HighScoreFileOut {
+        version: HIGH_SCORES_VERSION,
+        scores,
+    })?;
+    if parses_as_high_scores(path) {
+        fs::copy(path, with_suffix(path, ".bak"))?;
+    }
+    write_atomic(path, &json)
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+// CSV for spreadsheets, anything else is written as the same JSON the game saves
+pub fn export_scores(path: &Path, scores: &[ScoreEntry]) -> Result<(), String> {
+    let fail = |e: &dyn std::fmt::Display| format!("Couldn't export to {}: {}", path.display(), e);
+    if is_csv(path) {
+        let mut writer = csv::Writer::from_path(path).map_err(|e| fail(&e))?;
+        for entry in scores {
+            writer.serialize(entry).map_err(|e| fail(&e))?;
+        }
+        writer.flush().map_err(|e| fail(&e))
+    } else {
+        let json = serde_json::to_string_pretty(&HighScoreFileOut {
+            version: HIGH_SCORES_VERSION,
+            scores,
+        })
+        .map_err(|e| fail(&e))?;
+        fs::write(path, json).map_err(|e| fail(&e))
+    }
+}
+
+pub fn import_scores(path: &Path) -> Result<Vec<ScoreEntry>, String> {
+    let fail = |e: &dyn std::fmt::Display| format!("Couldn't import {}: {}", path.display(), e);
+    if is_csv(path) {
+        let mut reader = csv::Reader::from_path(path).map_err(|e| fail(&e))?;
+        reader
+            .deserialize()
+            .collect::<Result<Vec<ScoreEntry>, _>>()
+            .map_err(|e| fail(&e))
+    } else {
+        let contents = fs::read_to_string(path).map_err(|e| fail(&e))?;
+        parse_high_scores(&contents).map_err(|e| fail(&e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scores::{Difficulty, GameMode};
+
+    const ENTRY: &str = r#"{"player_name":"Ann","score":120,"difficulty":"Hard","timestamp":"2024-05-01T12:00:00+00:00"}"#;
+
+    #[test]
+    fn parses_bare_v1_array() {
+        let scores = parse_high_scores(&format!("[{}]", ENTRY)).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].player_name, "Ann");
+        assert_eq!(scores[0].score, 120);
+        assert_eq!(scores[0].difficulty, Difficulty::Hard);
+        assert_eq!(scores[0].mode, GameMode::Classic);
+    }
+
+    #[test]
+    fn migrates_v1_into_current_layout() {
+        let value = migrate_high_scores(1, serde_json::from_str(&format!("[{}]", ENTRY)).unwrap());
+        assert_eq!(value["version"], 2);
+        assert!(value["scores"].is_array());
+    }
+
+    #[test]
+    fn parses_v2_object() {
+        let scores = parse_high_scores(&format!(r#"{{"version":2,"scores":[{}]}}"#, ENTRY)).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].score, 120);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let contents = format!(r#"{{"version":{},"scores":[{}]}}"#, HIGH_SCORES_VERSION + 1, ENTRY);
+        assert!(parse_high_scores(&contents).is_err());
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_backup() {
+        let path = std::env::temp_dir().join(format!("snake_backup_test_{}.json", std::process::id()));
+        let backup = with_suffix(&path, ".bak");
+        let corrupt = with_suffix(&path, ".corrupt");
+        fs::write(&path, "{ not json").unwrap();
+        fs::write(&backup, format!(r#"{{"version":2,"scores":[{}]}}"#, ENTRY)).unwrap();
+
+        let loaded = load_high_scores(&path);
+        let mut newer: ScoreEntry = serde_json::from_str(ENTRY).unwrap();
+        newer.score = 30;
+        save_high_scores(&path, &[newer]).unwrap();
+        let kept = load_high_scores(&backup);
+        let saved = load_high_scores(&path);
+        for file in [&path, &backup, &corrupt] {
+            let _ = fs::remove_file(file);
+        }
+
+        let (scores, notice) = loaded.unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].score, 120);
+        assert!(notice.is_some());
+        // The save replaced the corrupt file but left the good backup alone
+        assert_eq!(kept.unwrap().0[0].score, 120);
+        assert_eq!(saved.unwrap().0[0].score, 30);
+    }
+
+    #[test]
+    fn csv_export_round_trips() {
+        let mut custom: ScoreEntry = serde_json::from_str(ENTRY).unwrap();
+        custom.difficulty = Difficulty::Custom;
+        custom.mode = GameMode::TimeAttack;
+        custom.custom_key = Some(0xdead_beef);
+        let mut adaptive: ScoreEntry = serde_json::from_str(ENTRY).unwrap();
+        adaptive.player_name = "Bo, Jr.".to_string();
+        adaptive.difficulty = Difficulty::Adaptive;
+        adaptive.adaptive_level = Some(4.5);
+        let scores = vec![serde_json::from_str(ENTRY).unwrap(), custom, adaptive];
+
+        let path = std::env::temp_dir().join(format!("snake_export_test_{}.csv", std::process::id()));
+        export_scores(&path, &scores).unwrap();
+        let imported = import_scores(&path);
+        let _ = fs::remove_file(&path);
+        let imported = imported.unwrap();
+
+        assert_eq!(imported.len(), scores.len());
+        for (a, b) in scores.iter().zip(&imported) {
+            assert_eq!(a.player_name, b.player_name);
+            assert_eq!(a.score, b.score);
+            assert_eq!(a.difficulty, b.difficulty);
+            assert_eq!(a.mode, b.mode);
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.custom_key, b.custom_key);
+            assert_eq!(a.adaptive_level, b.adaptive_level);
+        }
+    }
+}
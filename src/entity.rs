@@ -0,0 +1,96 @@
+// Things on the board that move by themselves: patrolling blocks and a mouse that runs from the snake
+use crate::expiring::Expiring;
+use crate::Position;
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+use snake_shared::replay::Direction;
+
+// Patrolling blocks placed at the start of a classic run with moving blocks turned on
+pub const PATROLLER_COUNT: usize = 3;
+// Chance of a mouse appearing when food is eaten, if there isn't one already
+pub const MOUSE_CHANCE: f64 = 0.1;
+pub const MOUSE_POINTS: u32 = 40;
+pub const MOUSE_GROWTH: usize = 2;
+// How close the head has to be before the mouse runs rather than wanders
+pub const MOUSE_SENSE_RADIUS: i16 = 6;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum EntityKind {
+    Patroller,
+    Mouse,
+}
+
+pub struct EntityInfo {
+    // Snake moves between each of its own, so the snake can keep up with it
+    pub move_every: u32,
+    // Moves it stays on the board; `None` for ones that stay all run
+    pub lifetime: Option<u32>,
+    pub color: Color,
+}
+
+impl EntityKind {
+    pub fn info(&self) -> EntityInfo {
+        match self {
+            EntityKind::Patroller => EntityInfo {
+                move_every: 2,
+                lifetime: None,
+                color: Color::new(0.75, 0.3, 0.3, 1.0),
+            },
+            EntityKind::Mouse => EntityInfo {
+                move_every: 2,
+                lifetime: Some(100),
+                color: Color::new(0.7, 0.7, 0.75, 1.0),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub position: Position,
+    // Where a patroller is heading; turned round whenever it's blocked
+    pub direction: Direction,
+    // Snake moves left before this one moves again
+    pub wait: u32,
+    #[serde(default)]
+    pub ticks_left: Option<u32>,
+}
+
+impl Entity {
+    pub fn new(kind: EntityKind, position: Position, direction: Direction) -> Self {
+        let info = kind.info();
+        Entity {
+            kind,
+            position,
+            direction,
+            wait: info.move_every,
+            ticks_left: info.lifetime,
+        }
+    }
+
+    // Called once per snake move; true when it's this one's turn to move
+    pub fn ready(&mut self) -> bool {
+        if self.wait > 1 {
+            self.wait -= 1;
+            return false;
+        }
+        self.wait = self.kind.info().move_every;
+        true
+    }
+}
+
+pub fn step(pos: Position, direction: Direction) -> Position {
+    match direction {
+        Direction::Up => Position { x: pos.x, y: pos.y - 1 },
+        Direction::Down => Position { x: pos.x, y: pos.y + 1 },
+        Direction::Left => Position { x: pos.x - 1, y: pos.y },
+        Direction::Right => Position { x: pos.x + 1, y: pos.y },
+    }
+}
+
+impl Expiring for Entity {
+    fn ticks_left(&mut self) -> Option<&mut u32> {
+        self.ticks_left.as_mut()
+    }
+}
@@ -0,0 +1,16 @@
+// Things that vanish after a set number of snake moves: food, pickups and their effects, hazards and the mouse
+pub trait Expiring {
+    // Moves left; `None` for ones that stay until something else removes them
+    fn ticks_left(&mut self) -> Option<&mut u32>;
+}
+
+// Counts down by one move, dropping whatever has run out
+pub fn tick_timers<T: Expiring>(items: &mut Vec<T>) {
+    items.retain_mut(|item| match item.ticks_left() {
+        Some(ticks_left) => {
+            *ticks_left = ticks_left.saturating_sub(1);
+            *ticks_left > 0
+        }
+        None => true,
+    });
+}
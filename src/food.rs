@@ -0,0 +1,101 @@
+// Kinds of food on the board: what each is worth, how much it grows the snake, and how it looks
+use crate::expiring::Expiring;
+use crate::Position;
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+// Chance that a replacement food is big instead of regular
+pub const BIG_FOOD_CHANCE: f64 = 0.15;
+// Chance of a golden bonus appearing when food is eaten, on top of the usual count
+pub const GOLDEN_FOOD_CHANCE: f64 = 0.12;
+// Moves of a golden food's lifetime left over for getting round the body and walls on the way to it
+pub const GOLDEN_REACH_MARGIN: u32 = 10;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum FoodKind {
+    Regular,
+    Golden,
+    Big,
+}
+
+pub struct FoodInfo {
+    pub points: u32,
+    // Segments added to the snake
+    pub growth: usize,
+    // Moves before it disappears; `None` for food that stays until eaten
+    pub lifetime: Option<u32>,
+    // Drawn size relative to a grid cell
+    pub size: f32,
+    // Cycled through to make the food shimmer
+    pub palette: [Color; 5],
+}
+
+impl FoodKind {
+    pub fn info(&self) -> FoodInfo {
+        match self {
+            FoodKind::Regular => FoodInfo {
+                points: 10,
+                growth: 1,
+                lifetime: None,
+                size: 1.0,
+                palette: [
+                    Color::new(1.0, 0.0, 0.0, 1.0),  // Red
+                    Color::new(1.0, 0.2, 0.2, 1.0),  // Light red
+                    Color::new(1.0, 0.4, 0.4, 1.0),  // Lighter red
+                    Color::new(1.0, 0.6, 0.6, 1.0),  // Even lighter red
+                    Color::new(1.0, 0.8, 0.8, 1.0),  // Very light red
+                ],
+            },
+            FoodKind::Golden => FoodInfo {
+                points: 50,
+                growth: 1,
+                lifetime: Some(35),
+                size: 1.0,
+                palette: [
+                    Color::new(1.0, 0.75, 0.0, 1.0),
+                    Color::new(1.0, 0.8, 0.2, 1.0),
+                    Color::new(1.0, 0.85, 0.4, 1.0),
+                    Color::new(1.0, 0.9, 0.6, 1.0),
+                    Color::new(1.0, 1.0, 0.8, 1.0),
+                ],
+            },
+            FoodKind::Big => FoodInfo {
+                points: 30,
+                growth: 3,
+                lifetime: None,
+                size: 1.3,
+                palette: [
+                    Color::new(1.0, 0.45, 0.0, 1.0),
+                    Color::new(1.0, 0.55, 0.15, 1.0),
+                    Color::new(1.0, 0.65, 0.3, 1.0),
+                    Color::new(1.0, 0.75, 0.45, 1.0),
+                    Color::new(1.0, 0.85, 0.6, 1.0),
+                ],
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Food {
+    pub kind: FoodKind,
+    pub position: Position,
+    #[serde(default)]
+    pub ticks_left: Option<u32>,
+}
+
+impl Food {
+    pub fn new(kind: FoodKind, position: Position) -> Self {
+        Food {
+            kind,
+            position,
+            ticks_left: kind.info().lifetime,
+        }
+    }
+}
+
+impl Expiring for Food {
+    fn ticks_left(&mut self) -> Option<&mut u32> {
+        self.ticks_left.as_mut()
+    }
+}
@@ -0,0 +1,73 @@
+// Harmful pickups that turn up more often on the harder difficulties
+use crate::expiring::Expiring;
+use crate::Position;
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_HAZARDS: usize = 4;
+pub const POISON_SEGMENTS: usize = 2;
+pub const POISON_PENALTY: u32 = 20;
+// Moves the controls stay reversed after eating rotten food
+pub const ROTTEN_DURATION: u32 = 35;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum HazardKind {
+    Poison,
+    Bomb,
+    Rotten,
+}
+
+pub struct HazardInfo {
+    pub label: &'static str,
+    // Moves it stays on the board before vanishing
+    pub lifetime: u32,
+    pub color: Color,
+}
+
+impl HazardKind {
+    pub const ALL: [HazardKind; 3] = [HazardKind::Poison, HazardKind::Bomb, HazardKind::Rotten];
+
+    pub fn info(&self) -> HazardInfo {
+        match self {
+            HazardKind::Poison => HazardInfo {
+                label: "Poison",
+                lifetime: 80,
+                color: Color::new(0.4, 0.9, 0.1, 1.0),
+            },
+            HazardKind::Bomb => HazardInfo {
+                label: "Bomb",
+                lifetime: 65,
+                color: Color::new(0.05, 0.05, 0.05, 1.0),
+            },
+            HazardKind::Rotten => HazardInfo {
+                label: "Reversed",
+                lifetime: 80,
+                color: Color::new(0.65, 0.45, 0.2, 1.0),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    pub position: Position,
+    #[serde(default)]
+    pub ticks_left: u32,
+}
+
+impl Hazard {
+    pub fn new(kind: HazardKind, position: Position) -> Self {
+        Hazard {
+            kind,
+            position,
+            ticks_left: kind.info().lifetime,
+        }
+    }
+}
+
+impl Expiring for Hazard {
+    fn ticks_left(&mut self) -> Option<&mut u32> {
+        Some(&mut self.ticks_left)
+    }
+}
@@ -1,33 +1,86 @@
 //Most up to date snake_game
 use ggez::audio::{self, SoundSource};
-use ggez::event::{self, EventHandler};
+use ggez::event::{self, EventHandler, GamepadId};
+use ggez::input::gamepad::gilrs::{Axis, Button};
 use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::input::mouse::MouseButton;
 use ggez::mint::{Point2, Vector2};
 use ggez::{graphics, Context, GameResult};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::f32::consts::PI;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+use chrono::{Local, NaiveDate};
+
+mod entity;
+mod expiring;
+mod food;
+mod hazard;
+mod menu;
+mod portal;
+mod powerup;
+mod profile;
+use entity::{Entity, EntityKind};
+use food::{Food, FoodKind};
+use hazard::{Hazard, HazardKind};
+use menu::{Menu, MenuEvent, MenuStack};
+use portal::PortalPair;
+use powerup::{ActiveEffect, PowerUp, PowerUpKind};
+use profile::{DeathCause, Profiles, RunStats};
+use snake_shared::adaptive::Adaptive;
+use snake_shared::daily::{self, DailyBoard, DAILY_DIFFICULTY};
+use snake_shared::leaderboard::{self, Submission, SubmitResponse};
+use snake_shared::replay::{Direction, Replay};
+use snake_shared::scores::{
+    personal_bests, trim_high_scores, CustomDifficulty, Difficulty, DifficultyInfo, GameMode, Period, ScoreEntry, MAX_SCORES_PER_DIFFICULTY,
+};
+use snake_shared::storage;
 
 const GRID_SIZE: i16 = 30;
 const GRID_CELL_SIZE: i16 = 20;
 const SCREEN_SIZE: i16 = GRID_SIZE * GRID_CELL_SIZE;
 const SUBMENU_TRANSITION_TIME: f32 = 0.3;
-const MAX_SCORES_PER_DIFFICULTY: usize = 5;
+const MAX_QUEUED_TURNS: usize = 3;
+const STICK_DEADZONE: f32 = 0.5;
+const RESUME_COUNTDOWN: f32 = 3.0;
+const SLOW_MOTION_FACTOR: f32 = 1.6;
+// Manhattan distance at which the magnet starts pulling food in
+const MAGNET_RADIUS: i16 = 6;
+const SHRINK_SEGMENTS: usize = 3;
+const MIN_SNAKE_LENGTH: usize = 3;
+const MAX_FOOD_COUNT: usize = 5;
+// Closest two ends of a portal pair may be, so a trip through one is worth taking
+const PORTAL_MIN_DISTANCE: i16 = 10;
+// Length of a Time Attack run, and what each golden food adds to the clock
+const TIME_ATTACK_SECONDS: f32 = 90.0;
+const TIME_ATTACK_GOLDEN_BONUS: f32 = 5.0;
+// In Survival the outermost open ring of the board is walled off this often, after a warning
+const SURVIVAL_SHRINK_INTERVAL: f32 = 15.0;
+const SURVIVAL_SHRINK_WARNING: f32 = 3.0;
+// Narrowest the Survival arena gets
+const SURVIVAL_MIN_ARENA: i16 = 10;
+// Segments lost for running into something in Zen
+const ZEN_PENALTY_SEGMENTS: usize = 3;
+// How much each +/- press changes the time between moves in Zen, and the limits it stays within
+const ZEN_SPEED_STEP: f32 = 1.15;
+const ZEN_MIN_COOLDOWN: f32 = 0.04;
+const ZEN_MAX_COOLDOWN: f32 = 0.5;
+// Items blink for their last few moves before vanishing
+const BLINK_TICKS: u32 = 12;
+// Name sent to the online leaderboard for runs without a profile or typed name
+const GUEST_NAME: &str = "Guest";
 
 // Colors
 const BACKGROUND_COLOR: graphics::Color = graphics::Color::new(0.1, 0.1, 0.15, 1.0);
 const GRID_COLOR: graphics::Color = graphics::Color::new(0.15, 0.15, 0.2, 1.0);
-const FOOD_COLORS: [graphics::Color; 5] = [
-    graphics::Color::new(1.0, 0.0, 0.0, 1.0),  // Red
-    graphics::Color::new(1.0, 0.2, 0.2, 1.0),  // Light red
-    graphics::Color::new(1.0, 0.4, 0.4, 1.0),  // Lighter red
-    graphics::Color::new(1.0, 0.6, 0.6, 1.0),  // Even lighter red
-    graphics::Color::new(1.0, 0.8, 0.8, 1.0),  // Very light red
-];
-
-#[derive(Clone, Copy, PartialEq)]
+const OVERLAY_COLOR: graphics::Color = graphics::Color::new(0.0, 0.0, 0.0, 0.6);
+const WALL_COLOR: graphics::Color = graphics::Color::new(0.45, 0.45, 0.5, 1.0);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 struct Position {
     x: i16,
     y: i16,
@@ -41,20 +94,58 @@ enum GameState {
     GameOver,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
-enum Difficulty {
-    Easy,
-    Medium,
-    Hard,
-    Expert,
+// Headless `--export-scores`/`--import-scores` commands; the format follows the file extension
+fn run_score_command(command: ScoreCommand, data_dir: &Path) -> Result<String, String> {
+    let scores_path = data_dir.join(storage::HIGH_SCORES_FILE);
+    let (mut scores, notice) = storage::load_high_scores(&scores_path)?;
+    if let Some(notice) = notice {
+        eprintln!("{}", notice);
+    }
+    match command {
+        ScoreCommand::Export(file) => {
+            storage::export_scores(&file, &scores)?;
+            Ok(format!("Exported {} scores to {}", scores.len(), file.display()))
+        }
+        ScoreCommand::Import(file) => {
+            let before = scores.len();
+            for entry in storage::import_scores(&file)? {
+                let duplicate = scores.iter().any(|existing| {
+                    existing.player_name == entry.player_name
+                        && existing.score == entry.score
+                        && existing.timestamp == entry.timestamp
+                });
+                if !duplicate {
+                    scores.push(entry);
+                }
+            }
+            trim_high_scores(&mut scores);
+            storage::save_high_scores(&scores_path, &scores).map_err(|e| e.to_string())?;
+            Ok(format!("Imported scores from {}: table went from {} to {} entries", file.display(), before, scores.len()))
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct ScoreEntry {
-    player_name: String,
-    score: u32,
-    difficulty: Difficulty,
-    timestamp: DateTime<Local>,
+// Cells `margin` steps in from the edge of the board
+fn ring_cells(margin: i16) -> Vec<Position> {
+    let far = GRID_SIZE - 1 - margin;
+    let mut cells = Vec::new();
+    for x in margin..=far {
+        for y in margin..=far {
+            if x == margin || x == far || y == margin || y == far {
+                cells.push(Position { x, y });
+            }
+        }
+    }
+    cells
+}
+
+fn index_of<T: PartialEq>(options: &[T], value: T) -> usize {
+    options.iter().position(|option| *option == value).unwrap_or(0)
+}
+
+enum ScoreCommand {
+    Export(PathBuf),
+    Import(PathBuf),
 }
 
 #[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
@@ -62,34 +153,135 @@ enum MenuState {
     Main,
     Difficulty,
     HighScores,
+    Settings,
     EnteringName,
+    Pause,
+    ConfirmQuit,
+    GameOver,
+    Profiles,
+    Stats,
+    Daily,
+    CustomDifficulty,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
-struct DifficultyInfo {
-    speed: f32,
-    score_multiplier: f32,
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum MenuAction {
+    Play,
+    PlayDaily,
+    PlayTimeAttack,
+    PlaySurvival,
+    PlayZen,
+    Continue,
+    Resume,
+    Restart,
+    SaveAndQuit,
+    MainMenu,
+    Open(MenuState),
+    Exit,
+    SetDifficulty(Difficulty),
+    Sound,
+    Volume,
+    FoodCount,
+    Portals,
+    MovingBlocks,
+    CustomStartSpeed,
+    CustomAcceleration,
+    CustomTopSpeed,
+    CustomGrowth,
+    CustomMultiplier,
+    PlayerName,
+    SkipName,
+    SelectProfile(usize),
+    NewProfile,
+    ScoreDifficulty,
+    ScoreMode,
+    ScorePeriod,
+    ScoreView,
 }
 
-impl Difficulty {
-    fn get_info(&self) -> DifficultyInfo {
-        match self {
-            Difficulty::Easy => DifficultyInfo {
-                speed: 0.2,
-                score_multiplier: 1.0,
-            },
-            Difficulty::Medium => DifficultyInfo {
-                speed: 0.15,
-                score_multiplier: 1.5,
-            },
-            Difficulty::Hard => DifficultyInfo {
-                speed: 0.1,
-                score_multiplier: 2.0,
-            },
-            Difficulty::Expert => DifficultyInfo {
-                speed: 0.07,
-                score_multiplier: 3.0,
-            },
+// Which slice of the high score table the leaderboard screen is showing
+#[derive(Clone, Copy)]
+struct ScoreFilter {
+    difficulty: Difficulty,
+    mode: GameMode,
+    period: Period,
+    personal_bests: bool,
+}
+
+// Everything needed to pick a run back up exactly where it was left
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    snake: Vec<Position>,
+    direction: Direction,
+    // Turns pressed but not yet taken; `direction` is always the heading the snake last moved in
+    #[serde(default)]
+    direction_queue: VecDeque<Direction>,
+    // Saves from before several foods could be on the board had just this one
+    #[serde(default, skip_serializing)]
+    food: Option<Position>,
+    #[serde(default)]
+    foods: Vec<Food>,
+    #[serde(default)]
+    pending_growth: usize,
+    // Saves from before this was fixed per run use the current setting
+    #[serde(default)]
+    food_count: Option<usize>,
+    score: u32,
+    movement_cooldown: f32,
+    difficulty: Difficulty,
+    rng: ChaCha8Rng,
+    #[serde(default)]
+    food_eaten: u32,
+    #[serde(default)]
+    time_played: f32,
+    #[serde(default)]
+    replay: Option<Replay>,
+    #[serde(default)]
+    power_ups: Vec<PowerUp>,
+    #[serde(default)]
+    effects: Vec<ActiveEffect>,
+    #[serde(default)]
+    hazards: Vec<Hazard>,
+    #[serde(default)]
+    reversed_ticks: u32,
+    #[serde(default)]
+    portals: Vec<PortalPair>,
+    #[serde(default)]
+    random_portals: bool,
+    #[serde(default)]
+    entities: Vec<Entity>,
+    #[serde(default)]
+    custom: CustomDifficulty,
+    #[serde(default)]
+    adaptive: Adaptive,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct Settings {
+    sound_enabled: bool,
+    volume: f32,
+    // Base URL of a `snake-server` to submit finished runs to, e.g. http://localhost:7878
+    leaderboard_url: Option<String>,
+    // Regular and big food kept on the board at once; golden bonuses come on top
+    food_count: usize,
+    // Random portal pair in classic runs
+    portals: bool,
+    // Patrolling blocks in classic runs
+    moving_blocks: bool,
+    custom_difficulty: CustomDifficulty,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            sound_enabled: true,
+            volume: 1.0,
+            leaderboard_url: None,
+            food_count: 1,
+            portals: false,
+            moving_blocks: false,
+            custom_difficulty: CustomDifficulty::default(),
         }
     }
 }
@@ -98,28 +290,90 @@ struct Game {
     state: GameState,
     snake: Vec<Position>,
     direction: Direction,
-    next_direction: Direction,
-    food: Position,
+    direction_queue: VecDeque<Direction>,
+    food: Vec<Food>,
+    // Segments still to be added, one per move, after eating
+    pending_growth: usize,
+    // Regular and big food the run keeps on the board, fixed when it starts
+    run_food_count: usize,
+    power_ups: Vec<PowerUp>,
+    effects: Vec<ActiveEffect>,
+    hazards: Vec<Hazard>,
+    // Moves left with the controls swapped after eating rotten food
+    reversed_ticks: u32,
     food_animation: f32,
     movement_cooldown: f32,
     initial_cooldown: f32,
     last_update: f32,
     score: u32,
+    // Difficulty of the run in progress; daily runs override the one picked in the menu
     difficulty: Difficulty,
+    selected_difficulty: Difficulty,
+    // Settings the run is using when its difficulty is Custom
+    custom: CustomDifficulty,
+    // Level and pace tracking for Adaptive runs
+    adaptive: Adaptive,
+    mode: GameMode,
+    // Blocked cells, e.g. the daily board's layout
+    walls: Vec<Position>,
+    portals: Vec<PortalPair>,
+    // Whether the run has the random classic portals, fixed when it starts
+    random_portals: bool,
+    // Patrolling blocks and the mouse, moved in step with the snake
+    entities: Vec<Entity>,
     high_score: u32,
     eat_sound: audio::Source,
     game_over_sound: audio::Source,
-    menu_selection: usize,
     particle_effects: Vec<ParticleEffect>,
-    menu_state: MenuState,
+    menu_stack: MenuStack<MenuState>,
     high_scores: Vec<ScoreEntry>,
+    daily_board: DailyBoard,
+    // Day the current daily run counts for, and whether it's the player's official attempt
+    daily_date: NaiveDate,
+    daily_official: bool,
+    score_filter: ScoreFilter,
     submenu_transition: f32,
-    player_name: String,
-    name_input_active: bool,
+    profiles: Profiles,
+    // Name typed for a new profile, either on the Profiles screen or at the name prompt
+    new_profile_name: String,
+    // Stats for a run that ended before any profile existed, recorded once one is created
+    unrecorded_run: Option<RunStats>,
+    food_eaten: u32,
+    time_played: f32,
+    // Seconds left on the clock in Time Attack
+    time_left: f32,
+    // Rings walled off so far in Survival, and seconds until the next one closes
+    arena_margin: i16,
+    next_shrink: f32,
+    // Zen snake that bumped into something, waiting for a turn before it moves again
+    stalled: bool,
+    // How the last run ended, shown on the game over screen
+    death_cause: Option<DeathCause>,
+    // Rank banner for the last saved score, shown on the game over screen
+    submitted_banner: Option<String>,
+    // Player N is the gamepad at index N - 1; pads join in the order they're first used
+    gamepad_players: Vec<GamepadId>,
+    // Pad that started the current run, `None` when started from the keyboard
+    active_gamepad: Option<GamepadId>,
+    held_stick_axes: Vec<(GamepadId, Axis)>,
+    settings: Settings,
+    // Drives gameplay randomness so a saved game resumes with the same food sequence
+    rng: ChaCha8Rng,
+    has_saved_game: bool,
+    // Problem reading or writing the high score file, shown on the menus until restart
+    storage_error: Option<String>,
+    data_dir: PathBuf,
+    // Seconds left before play picks back up after leaving the pause menu
+    resume_countdown: f32,
+    // Recording of the current run, `None` when resumed from a save made before replays existed
+    replay: Option<Replay>,
+    leaderboard_url: Option<String>,
+    pending_submission: Option<Receiver<Result<SubmitResponse, String>>>,
+    // Outcome of the last online submission, shown on the game over screen
+    leaderboard_status: Option<String>,
 }
 
 struct ParticleEffect {
-    position: Position,
     particles: Vec<Particle>,
     lifetime: f32,
 }
@@ -158,7 +412,6 @@ impl ParticleEffect {
         }
         
         ParticleEffect {
-            position,
             particles,
             lifetime: 1.0,
         }
@@ -176,221 +429,835 @@ impl ParticleEffect {
 }
 
 impl Game {
-    pub fn new(ctx: &mut Context) -> GameResult<Self> {
-        let eat_sound = audio::Source::new(ctx, "/eat.wav")?;
-        let game_over_sound = audio::Source::new(ctx, "/game_over.wav")?;
-        let high_scores = Self::load_high_scores().unwrap_or_default();
+    pub fn new(ctx: &mut Context, data_dir: PathBuf, leaderboard_url: Option<String>) -> GameResult<Self> {
+        let mut eat_sound = audio::Source::new(ctx, "/eat.wav")?;
+        let mut game_over_sound = audio::Source::new(ctx, "/game_over.wav")?;
+        let mut load_errors = Vec::new();
+        let high_scores = match storage::load_high_scores(&data_dir.join(storage::HIGH_SCORES_FILE)) {
+            Ok((scores, notice)) => {
+                load_errors.extend(notice);
+                scores
+            }
+            Err(e) => {
+                load_errors.push(e);
+                Vec::new()
+            }
+        };
+        let daily_board = DailyBoard::load(&data_dir.join(storage::DAILY_SCORES_FILE)).unwrap_or_else(|e| {
+            load_errors.push(e);
+            DailyBoard::default()
+        });
+        let profiles = Profiles::load(&data_dir.join(storage::PROFILES_FILE)).unwrap_or_else(|e| {
+            load_errors.push(e);
+            Profiles::default()
+        });
+        let storage_error = (!load_errors.is_empty()).then(|| load_errors.join(" "));
+        let settings = Self::load_settings(&data_dir);
+        eat_sound.set_volume(settings.volume);
+        game_over_sound.set_volume(settings.volume);
+        // The --leaderboard flag wins over the URL saved in settings
+        let leaderboard_url = leaderboard_url.or_else(|| settings.leaderboard_url.clone());
 
         Ok(Game {
             state: GameState::Menu,
             snake: Vec::new(),
             direction: Direction::Right,
-            next_direction: Direction::Right,
-            food: Position { x: 0, y: 0 },
+            direction_queue: VecDeque::new(),
+            food: Vec::new(),
+            pending_growth: 0,
+            run_food_count: 1,
+            power_ups: Vec::new(),
+            effects: Vec::new(),
+            hazards: Vec::new(),
+            reversed_ticks: 0,
             food_animation: 0.0,
             movement_cooldown: 0.15,
             initial_cooldown: 0.15,
             last_update: 0.0,
             score: 0,
             difficulty: Difficulty::Medium,
+            selected_difficulty: Difficulty::Medium,
+            custom: CustomDifficulty::default(),
+            adaptive: Adaptive::default(),
+            mode: GameMode::Classic,
+            walls: Vec::new(),
+            portals: Vec::new(),
+            random_portals: false,
+            entities: Vec::new(),
             high_score: 0,
             eat_sound,
             game_over_sound,
-            menu_selection: 0,
             particle_effects: Vec::new(),
-            menu_state: MenuState::Main,
+            menu_stack: MenuStack::new(MenuState::Main),
             high_scores,
+            daily_board,
+            daily_date: Local::now().date_naive(),
+            daily_official: false,
+            score_filter: ScoreFilter {
+                difficulty: Difficulty::Medium,
+                mode: GameMode::Classic,
+                period: Period::AllTime,
+                personal_bests: false,
+            },
             submenu_transition: 0.0,
-            player_name: String::new(),
-            name_input_active: false,
+            profiles,
+            new_profile_name: String::new(),
+            unrecorded_run: None,
+            time_left: 0.0,
+            arena_margin: 0,
+            next_shrink: 0.0,
+            stalled: false,
+            death_cause: None,
+            food_eaten: 0,
+            time_played: 0.0,
+            submitted_banner: None,
+            gamepad_players: Vec::new(),
+            active_gamepad: None,
+            held_stick_axes: Vec::new(),
+            settings,
+            rng: ChaCha8Rng::from_entropy(),
+            has_saved_game: data_dir.join(storage::SAVEGAME_FILE).is_file(),
+            resume_countdown: 0.0,
+            replay: None,
+            leaderboard_url,
+            pending_submission: None,
+            leaderboard_status: None,
+            storage_error,
+            data_dir,
         })
     }
-    fn load_high_scores() -> std::io::Result<Vec<ScoreEntry>> {
-        match fs::read_to_string("high_scores.json") {
-            Ok(contents) => Ok(serde_json::from_str(&contents).unwrap_or_default()),
-            Err(_) => Ok(Vec::new()),
+    fn save_high_scores(&mut self) {
+        if let Err(e) = storage::save_high_scores(&self.data_dir.join(storage::HIGH_SCORES_FILE), &self.high_scores) {
+            eprintln!("Failed to save high scores: {}", e);
+            self.storage_error = Some(format!("Failed to save high scores: {}", e));
         }
     }
 
-    fn save_high_scores(&self) -> std::io::Result<()> {
-        let json = serde_json::to_string_pretty(&self.high_scores)?;
-        fs::write("high_scores.json", json)
+    fn load_settings(data_dir: &Path) -> Settings {
+        fs::read_to_string(data_dir.join(storage::SETTINGS_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
     }
 
-    fn add_high_score(&mut self, score: u32) {
-        if self.player_name.is_empty() {
-            self.menu_state = MenuState::EnteringName;
-            self.name_input_active = true;
-            return;
+    fn save_settings(&self) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.settings)?;
+        storage::write_atomic(&self.data_dir.join(storage::SETTINGS_FILE), &json)
+    }
+
+    fn save_game(&mut self) -> std::io::Result<()> {
+        let saved = SavedGame {
+            snake: self.snake.clone(),
+            direction: self.direction,
+            direction_queue: self.direction_queue.clone(),
+            food: None,
+            foods: self.food.clone(),
+            pending_growth: self.pending_growth,
+            food_count: Some(self.run_food_count),
+            score: self.score,
+            movement_cooldown: self.movement_cooldown,
+            difficulty: self.difficulty,
+            rng: self.rng.clone(),
+            food_eaten: self.food_eaten,
+            time_played: self.time_played,
+            replay: self.replay.clone(),
+            power_ups: self.power_ups.clone(),
+            effects: self.effects.clone(),
+            hazards: self.hazards.clone(),
+            reversed_ticks: self.reversed_ticks,
+            portals: self.portals.clone(),
+            random_portals: self.random_portals,
+            entities: self.entities.clone(),
+            custom: self.custom,
+            adaptive: self.adaptive,
+        };
+        let json = serde_json::to_string_pretty(&saved)?;
+        storage::write_atomic(&self.data_dir.join(storage::SAVEGAME_FILE), &json)?;
+        self.has_saved_game = true;
+        Ok(())
+    }
+
+    // A save is consumed when it's resumed, so a run can't be replayed from the same point
+    fn load_game(&mut self) -> std::io::Result<()> {
+        let path = self.data_dir.join(storage::SAVEGAME_FILE);
+        let contents = fs::read_to_string(&path)?;
+        let saved: SavedGame = serde_json::from_str(&contents)?;
+        fs::remove_file(&path)?;
+        self.has_saved_game = false;
+
+        // Only classic runs can be saved
+        self.mode = GameMode::Classic;
+        self.reset();
+        self.snake = saved.snake;
+        self.direction = saved.direction;
+        self.direction_queue = saved.direction_queue;
+        self.food = match saved.food {
+            Some(position) if saved.foods.is_empty() => vec![Food::new(FoodKind::Regular, position)],
+            _ => saved.foods,
+        };
+        self.pending_growth = saved.pending_growth;
+        if let Some(count) = saved.food_count {
+            self.run_food_count = count;
+        }
+        self.score = saved.score;
+        self.movement_cooldown = saved.movement_cooldown;
+        self.difficulty = saved.difficulty;
+        self.custom = saved.custom;
+        self.adaptive = saved.adaptive;
+        self.initial_cooldown = self.difficulty_info().speed;
+        self.rng = saved.rng;
+        self.food_eaten = saved.food_eaten;
+        self.time_played = saved.time_played;
+        self.replay = saved.replay;
+        self.power_ups = saved.power_ups;
+        self.effects = saved.effects;
+        self.hazards = saved.hazards;
+        self.reversed_ticks = saved.reversed_ticks;
+        self.portals = saved.portals;
+        self.random_portals = saved.random_portals;
+        self.entities = saved.entities;
+        Ok(())
+    }
+
+    // Position the score would take in its table, if it makes the cut
+    fn high_score_rank(&self, score: u32, difficulty: Difficulty, mode: GameMode) -> Option<usize> {
+        let custom_key = (difficulty == Difficulty::Custom).then(|| self.custom.key());
+        let ahead = self.high_scores.iter()
+            .filter(|entry| entry.in_table(difficulty, mode, custom_key) && entry.score >= score)
+            .count();
+        (ahead < MAX_SCORES_PER_DIFFICULTY).then_some(ahead + 1)
+    }
+
+    fn qualifies(&self, score: u32, difficulty: Difficulty, mode: GameMode) -> bool {
+        // Every official daily attempt goes on the daily board, whatever the score
+        if mode == GameMode::Daily {
+            return self.daily_official;
+        }
+        score > 0 && self.high_score_rank(score, difficulty, mode).is_some()
+    }
+
+    // Scores are saved under the active profile, or the name being typed if there isn't one
+    fn player_name(&self) -> &str {
+        match self.profiles.active() {
+            Some(profile) => &profile.name,
+            None => &self.new_profile_name,
+        }
+    }
+
+    fn save_profiles(&self) {
+        self.profiles.save(&self.data_dir.join(storage::PROFILES_FILE)).unwrap_or_else(|e| eprintln!("Failed to save profiles: {}", e));
+    }
+
+    fn save_daily_board(&self) {
+        self.daily_board.save(&self.data_dir.join(storage::DAILY_SCORES_FILE)).unwrap_or_else(|e| eprintln!("Failed to save daily scores: {}", e));
+    }
+
+    fn rank_banner(&self, score: u32) -> String {
+        if self.mode == GameMode::Daily {
+            if !self.daily_official {
+                return match self.profiles.active() {
+                    Some(_) => "Already played today, so this run was practice",
+                    None => "Practice run: select a profile for an official attempt",
+                }
+                .to_string();
+            }
+            return format!("Daily rank #{} for {}", self.daily_board.rank(self.daily_date, score), self.daily_date);
+        }
+        let rank = self.high_score_rank(score, self.difficulty, self.mode).unwrap_or(1);
+        let previous_best = self.high_scores.iter()
+            .filter(|entry| entry.player_name == self.player_name() && entry.in_table(self.difficulty, self.mode, self.custom_key()))
+            .map(|entry| entry.score)
+            .max();
+        let table = match self.mode {
+            GameMode::Classic => format!("{:?}", self.difficulty),
+            mode => format!("{:?} {}", self.difficulty, mode.label()),
+        };
+        if previous_best.is_none_or(|best| score > best) {
+            format!("New personal best! Rank #{} on {}", rank, table)
+        } else {
+            format!("Rank #{} on {}", rank, table)
+        }
+    }
+
+    fn add_high_score(&mut self, score: u32) -> bool {
+        if !self.qualifies(score, self.difficulty, self.mode) {
+            return false;
         }
 
         let entry = ScoreEntry {
-            player_name: self.player_name.clone(),
+            player_name: self.player_name().to_string(),
             score,
             difficulty: self.difficulty,
+            mode: self.mode,
             timestamp: Local::now(),
+            custom_key: self.custom_key(),
+            adaptive_level: self.adaptive_level(),
         };
 
-        self.high_scores.push(entry);
-        self.high_scores.sort_by(|a, b| b.score.cmp(&a.score));
-
-        // Keep only top scores per difficulty
-        let mut filtered_scores = Vec::new();
-        for diff in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Expert] {
-            let mut count = 0;
-            for score in self.high_scores.iter() {
-                if score.difficulty == diff {
-                    if count < MAX_SCORES_PER_DIFFICULTY {
-                        filtered_scores.push(score.clone());
-                        count += 1;
-                    }
-                }
+        if self.mode == GameMode::Daily {
+            if !self.daily_board.record(self.daily_date, entry) {
+                return false;
             }
+            self.save_daily_board();
+            return true;
         }
-        self.high_scores = filtered_scores;
-        self.save_high_scores().unwrap_or_else(|e| eprintln!("Failed to save high scores: {}", e));
+
+        self.high_scores.push(entry);
+        trim_high_scores(&mut self.high_scores);
+        self.save_high_scores();
+        true
     }
 
-fn draw_difficulty_menu(&self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
-    let mut title_text = graphics::Text::new("Select Difficulty");
-    let title = title_text.set_scale(40.0);
-    canvas.draw(
-        title,  // No & needed, set_scale returns &mut Text
-        graphics::DrawParam::default()
-            .dest(Point2 {
-                x: (SCREEN_SIZE as f32 / 2.0) - 100.0,
-                y: 50.0,
-            })
-            .color(graphics::Color::WHITE),
-    );
-
-    let difficulties = [
-        (Difficulty::Easy, "Easy"),
-        (Difficulty::Medium, "Medium"),
-        (Difficulty::Hard, "Hard"),
-        (Difficulty::Expert, "Expert"),
-    ];
-
-    for (i, (diff, name)) in difficulties.iter().enumerate() {
-        let info = diff.get_info();
-        let color = if *diff == self.difficulty {
-            graphics::Color::GREEN
-        } else {
-            graphics::Color::WHITE
-        };
+    // The official attempt needs a profile to hold it to; without one every daily run is practice,
+    // or skipping the name prompt would hand out another official attempt
+    fn daily_attempt_open(&self) -> bool {
+        self.profiles.active().is_some_and(|profile| !self.daily_board.played(self.daily_date, &profile.name))
+    }
 
-        let mut diff_text = graphics::Text::new(format!(
-            "{}: Speed {:.1}x, Score {:.1}x",
-            name,
-            1.0 / info.speed,
-            info.score_multiplier
-        ));
-        let diff_text = diff_text.set_scale(24.0);
-        
-        canvas.draw(
-            diff_text,  // No & needed, set_scale returns &mut Text
-            graphics::DrawParam::default()
-                .dest(Point2 {
-                    x: (SCREEN_SIZE as f32 / 2.0) - 150.0,
-                    y: 150.0 + (i as f32 * 50.0),
+    // Quitting an official daily attempt early still uses it up
+    fn forfeits_daily(&self) -> bool {
+        self.state == GameState::Paused && self.mode == GameMode::Daily && self.daily_official
+    }
+
+    fn save_score(&mut self) {
+        // Work out the banner before the score lands in the table it's compared against
+        let banner = self.rank_banner(self.score);
+        // Daily runs always say whether they counted
+        if self.add_high_score(self.score) || self.mode == GameMode::Daily {
+            self.submitted_banner = Some(banner);
+        }
+    }
+
+    fn build_menu(&self, screen: MenuState) -> Menu<MenuAction> {
+        match screen {
+            MenuState::Main => {
+                let mut menu = Menu::new("SNAKE GAME").notice(self.storage_error.clone());
+                if self.has_saved_game {
+                    menu = menu.button("Continue", MenuAction::Continue);
+                }
+                menu.button("Play Game", MenuAction::Play)
+                    .button("Time Attack", MenuAction::PlayTimeAttack)
+                    .button("Survival", MenuAction::PlaySurvival)
+                    .button("Zen", MenuAction::PlayZen)
+                    .button("Daily Challenge", MenuAction::Open(MenuState::Daily))
+                    .button("Difficulty", MenuAction::Open(MenuState::Difficulty))
+                    .button("High Scores", MenuAction::Open(MenuState::HighScores))
+                    .button("Profiles", MenuAction::Open(MenuState::Profiles))
+                    .button("Settings", MenuAction::Open(MenuState::Settings))
+                    .button("Exit", MenuAction::Exit)
+            }
+            MenuState::Pause => {
+                let mut menu = Menu::new("Paused").button("Resume", MenuAction::Resume);
+                // An official daily attempt can't be restarted, and only classic runs can be saved for later
+                if !(self.mode == GameMode::Daily && self.daily_official) {
+                    menu = menu.button("Restart", MenuAction::Restart);
+                }
+                menu = menu.button("Settings", MenuAction::Open(MenuState::Settings));
+                if self.mode == GameMode::Classic {
+                    menu = menu.button("Save & Quit", MenuAction::SaveAndQuit);
+                }
+                menu.button("Main Menu", MenuAction::Open(MenuState::ConfirmQuit))
+            }
+            MenuState::ConfirmQuit => Menu::new("Quit to Main Menu?")
+                .label(if self.forfeits_daily() {
+                    "Today's attempt will be recorded as it stands."
+                } else {
+                    "Your current run will be lost."
                 })
-                .color(color),
-        );
+                .button("Yes, quit", MenuAction::MainMenu)
+                .button("Cancel", MenuAction::Resume)
+                .footer("Press ESC to return"),
+            MenuState::Difficulty => {
+                let mut menu = Menu::new("Select Difficulty");
+                for diff in Difficulty::ALL {
+                    let info = match diff {
+                        Difficulty::Custom => self.settings.custom_difficulty.info(),
+                        diff => diff.get_info(),
+                    };
+                    let marker = if diff == self.selected_difficulty { "* " } else { "" };
+                    let label = match diff {
+                        Difficulty::Adaptive => format!("{}Adaptive: keeps pace with how you play", marker),
+                        _ => format!("{}{:?}: Speed {:.1}x, Score {:.1}x", marker, diff, 1.0 / info.speed, info.score_multiplier),
+                    };
+                    menu = menu.button(label, MenuAction::SetDifficulty(diff));
+                }
+                menu.button("Edit Custom...", MenuAction::Open(MenuState::CustomDifficulty))
+                    .footer("Press ESC to return")
+            }
+            MenuState::CustomDifficulty => {
+                let custom = self.settings.custom_difficulty;
+                let (start_speed, top_speed) = (1.0 / custom.start_cooldown, 1.0 / custom.min_cooldown);
+                Menu::new("Custom Difficulty")
+                    .slider(format!("Start speed: {:.0} moves/s", start_speed), start_speed, 2.0, 20.0, 1.0, MenuAction::CustomStartSpeed)
+                    .slider(format!("Speed-up per food: {:.0}%", custom.acceleration * 100.0), custom.acceleration * 100.0, 0.0, 20.0, 1.0, MenuAction::CustomAcceleration)
+                    .slider(format!("Top speed: {:.0} moves/s", top_speed), top_speed, 5.0, 30.0, 1.0, MenuAction::CustomTopSpeed)
+                    .slider(format!("Growth per food: {}", custom.growth), custom.growth as f32, 1.0, 5.0, 1.0, MenuAction::CustomGrowth)
+                    .slider(format!("Score multiplier: {:.1}x", custom.score_multiplier), custom.score_multiplier, 0.5, 5.0, 0.5, MenuAction::CustomMultiplier)
+                    .label(format!("High score table {:08x}", custom.key() >> 32))
+                    .footer("Press ESC to return")
+            }
+            MenuState::HighScores => {
+                let filter = self.score_filter;
+                let now = Local::now();
+                // The Custom tab shows the table for the settings currently in the editor
+                let custom_key = (filter.difficulty == Difficulty::Custom).then(|| self.settings.custom_difficulty.key());
+                let matching = self.high_scores.iter().filter(|entry| {
+                    entry.in_table(filter.difficulty, filter.mode, custom_key) && filter.period.contains(&entry.timestamp, &now)
+                });
+                let entries = if filter.personal_bests { personal_bests(matching) } else { matching.collect() };
+                let rows = entries.iter()
+                    .enumerate()
+                    .map(|(j, score)| format!(
+                        "{:2}. {:8} {:6} {}{}",
+                        j + 1,
+                        score.player_name,
+                        score.score,
+                        score.timestamp.format("%Y-%m-%d %H:%M"),
+                        score.adaptive_level.map_or(String::new(), |level| format!(" L{:.1}", level)),
+                    ))
+                    .collect();
+
+                Menu::new("High Scores")
+                    .align_left()
+                    .notice(self.storage_error.clone())
+                    .choice(
+                        "Difficulty",
+                        Difficulty::ALL.iter().map(|diff| format!("{:?}", diff)).collect(),
+                        index_of(&Difficulty::ALL, filter.difficulty),
+                        MenuAction::ScoreDifficulty,
+                    )
+                    .choice(
+                        "Mode",
+                        GameMode::ALL.iter().map(|mode| mode.label().to_string()).collect(),
+                        index_of(&GameMode::ALL, filter.mode),
+                        MenuAction::ScoreMode,
+                    )
+                    .choice(
+                        "Period",
+                        Period::ALL.iter().map(|period| period.label().to_string()).collect(),
+                        index_of(&Period::ALL, filter.period),
+                        MenuAction::ScorePeriod,
+                    )
+                    .choice(
+                        "Show",
+                        vec!["Top scores".to_string(), "Personal bests".to_string()],
+                        filter.personal_bests as usize,
+                        MenuAction::ScoreView,
+                    )
+                    .scroll_list(rows, "No scores for this period")
+                    .footer("Press ESC to return")
+            }
+            MenuState::Settings => Menu::new("Settings")
+                .toggle("Sound", self.settings.sound_enabled, MenuAction::Sound)
+                .slider("Volume", self.settings.volume, 0.0, 1.0, 0.1, MenuAction::Volume)
+                .slider("Food on board", self.settings.food_count as f32, 1.0, MAX_FOOD_COUNT as f32, 1.0, MenuAction::FoodCount)
+                .toggle("Portals", self.settings.portals, MenuAction::Portals)
+                .toggle("Moving blocks", self.settings.moving_blocks, MenuAction::MovingBlocks)
+                .label(format!("Online leaderboard: {}", self.leaderboard_url.as_deref().unwrap_or("off")))
+                .footer("Press ESC to return"),
+            MenuState::EnteringName => {
+                let menu = Menu::new("Game Over!")
+                    .label(format!("Score: {}", self.score))
+                    .label(self.rank_banner(self.score));
+                match self.profiles.active() {
+                    Some(profile) => menu.button(format!("Save as {}", profile.name), MenuAction::PlayerName),
+                    None => menu
+                        .text_field("Name", &self.new_profile_name, 8, MenuAction::PlayerName)
+                        .button("Save Score", MenuAction::PlayerName),
+                }
+                .button("Skip", MenuAction::SkipName)
+            }
+            MenuState::Profiles => {
+                let mut menu = Menu::new("Profiles");
+                for (i, profile) in self.profiles.profiles.iter().enumerate() {
+                    let marker = if self.profiles.active == Some(i) { "* " } else { "" };
+                    menu = menu.button(format!("{}{}", marker, profile.name), MenuAction::SelectProfile(i));
+                }
+                menu.text_field("New profile", &self.new_profile_name, 8, MenuAction::NewProfile)
+                    .button("Create Profile", MenuAction::NewProfile)
+                    .button("Stats", MenuAction::Open(MenuState::Stats))
+                    .footer("Press ESC to return")
+            }
+            MenuState::Stats => {
+                let Some(profile) = self.profiles.active() else {
+                    return Menu::new("Stats")
+                        .label("No profile selected")
+                        .footer("Press ESC to return");
+                };
+                let minutes = (profile.time_played / 60.0) as u32;
+                let mut menu = Menu::new(format!("Stats: {}", profile.name))
+                    .label(format!("Games played: {}", profile.games_played))
+                    .label(format!("Food eaten: {}", profile.food_eaten))
+                    .label(format!("Longest snake: {}", profile.longest_snake))
+                    .label(format!("Time played: {}h {:02}m", minutes / 60, minutes % 60))
+                    .heading("Best scores: Classic");
+                for diff in Difficulty::ALL {
+                    let best = profile.best_scores.get(&diff).copied().unwrap_or(0);
+                    menu = menu.label(format!("{:?}: {}", diff, best));
+                }
+                // Other modes only list the difficulties they've been played on
+                for mode in [GameMode::TimeAttack, GameMode::Survival, GameMode::Daily] {
+                    let Some(bests) = profile.mode_bests.get(&mode) else {
+                        continue;
+                    };
+                    menu = menu.heading(format!("Best scores: {}", mode.label()));
+                    for diff in Difficulty::ALL {
+                        if let Some(best) = bests.get(&diff) {
+                            menu = menu.label(format!("{:?}: {}", diff, best));
+                        }
+                    }
+                }
+                menu = menu.heading("Deaths");
+                for cause in DeathCause::ALL {
+                    let count = profile.deaths.get(&cause).copied().unwrap_or(0);
+                    menu = menu.label(format!("{}: {}", cause.label(), count));
+                }
+                menu.footer("Press ESC to return")
+            }
+            MenuState::Daily => {
+                let today = Local::now().date_naive();
+                let status = match self.profiles.active() {
+                    Some(profile) if self.daily_board.played(today, &profile.name) => {
+                        format!("{} has played today; more runs are practice", profile.name)
+                    }
+                    Some(profile) => format!("{}'s next run is the official attempt", profile.name),
+                    None => "Select a profile to make an official attempt; runs without one are practice".to_string(),
+                };
+                let rows = self.daily_board.day(today)
+                    .iter()
+                    .enumerate()
+                    .map(|(j, entry)| format!("{:2}. {:8} {:6}", j + 1, entry.player_name, entry.score))
+                    .collect();
+                Menu::new("Daily Challenge")
+                    .align_left()
+                    .label(format!("{} on {:?}", today.format("%A %Y-%m-%d"), DAILY_DIFFICULTY))
+                    .label(status)
+                    .button("Play", MenuAction::PlayDaily)
+                    .heading("Today's board")
+                    .scroll_list(rows, "Nobody has played today yet")
+                    .footer("Press ESC to return")
+            }
+            MenuState::GameOver => {
+                let result = match &self.submitted_banner {
+                    Some(banner) => banner.clone(),
+                    None => format!("Best this session: {}", self.high_score),
+                };
+                let title = if self.death_cause == Some(DeathCause::TimeUp) { "Time's Up!" } else { "Game Over!" };
+                let mut menu = Menu::new(title);
+                if let Some(cause) = self.death_cause {
+                    menu = menu.label(cause.label());
+                }
+                menu = menu
+                    .label(format!("Score: {}", self.score))
+                    .label(result);
+                if let Some(status) = &self.leaderboard_status {
+                    menu = menu.label(status.clone());
+                }
+                menu.button("Restart", MenuAction::Restart)
+                    .button("Main Menu", MenuAction::MainMenu)
+            }
+        }
     }
 
-    let mut back_text = graphics::Text::new("Press ESC to return");
-    let back_text = back_text.set_scale(20.0);
-    canvas.draw(
-        back_text,  // No & needed, set_scale returns &mut Text
-        graphics::DrawParam::default()
-            .dest(Point2 {
-                x: (SCREEN_SIZE as f32 / 2.0) - 80.0,
-                y: SCREEN_SIZE as f32 - 50.0,
-            })
-            .color(graphics::Color::YELLOW),
-    );
+    fn apply_menu_event(&mut self, event: MenuEvent<MenuAction>, gamepad: Option<GamepadId>) {
+        match event {
+            MenuEvent::Activate(MenuAction::Play) => self.start_run(GameMode::Classic, gamepad),
+            MenuEvent::Activate(MenuAction::PlayDaily) => self.start_run(GameMode::Daily, gamepad),
+            MenuEvent::Activate(MenuAction::PlayTimeAttack) => self.start_run(GameMode::TimeAttack, gamepad),
+            MenuEvent::Activate(MenuAction::PlaySurvival) => self.start_run(GameMode::Survival, gamepad),
+            MenuEvent::Activate(MenuAction::PlayZen) => self.start_run(GameMode::Zen, gamepad),
+            MenuEvent::Activate(MenuAction::Continue) => match self.load_game() {
+                Ok(()) => {
+                    self.active_gamepad = gamepad;
+                    self.start_countdown();
+                }
+                Err(e) => {
+                    eprintln!("Failed to load saved game: {}", e);
+                    self.has_saved_game = false;
+                }
+            },
+            MenuEvent::Activate(MenuAction::Resume) if self.menu_stack.current() == MenuState::ConfirmQuit => {
+                self.menu_stack.pop();
+            }
+            MenuEvent::Activate(MenuAction::Resume) | MenuEvent::Back if self.menu_stack.current() == MenuState::Pause => {
+                self.menu_stack.reset();
+                self.start_countdown();
+            }
+            MenuEvent::Activate(MenuAction::Restart) => {
+                if self.state == GameState::GameOver {
+                    self.active_gamepad = gamepad;
+                }
+                if self.mode == GameMode::Daily {
+                    self.daily_official = self.daily_attempt_open();
+                }
+                self.menu_stack.reset();
+                self.reset();
+                self.start_countdown();
+            }
+            MenuEvent::Activate(MenuAction::SaveAndQuit) => match self.save_game() {
+                Ok(()) => {
+                    self.menu_stack.reset();
+                    self.state = GameState::Menu;
+                }
+                Err(e) => eprintln!("Failed to save game: {}", e),
+            },
+            MenuEvent::Activate(MenuAction::MainMenu) => {
+                if self.forfeits_daily() {
+                    self.add_high_score(self.score);
+                }
+                self.menu_stack.reset();
+                self.state = GameState::Menu;
+            }
+            MenuEvent::Activate(MenuAction::Open(screen)) => {
+                self.menu_stack.push(screen);
+                if screen == MenuState::Difficulty {
+                    self.menu_stack.frame_mut().select(index_of(&Difficulty::ALL, self.selected_difficulty));
+                }
+                // Start on the table for whatever was played last
+                if screen == MenuState::HighScores && GameMode::ALL.contains(&self.mode) {
+                    self.score_filter.difficulty = self.difficulty;
+                    self.score_filter.mode = self.mode;
+                }
+            }
+            MenuEvent::Activate(MenuAction::Exit) => std::process::exit(0),
+            MenuEvent::Activate(MenuAction::SetDifficulty(diff)) => self.selected_difficulty = diff,
+            MenuEvent::Activate(MenuAction::PlayerName) if !self.player_name().is_empty() => {
+                self.save_score();
+                if self.profiles.active().is_none() {
+                    self.create_profile();
+                }
+                self.show_results();
+            }
+            MenuEvent::Activate(MenuAction::NewProfile) if !self.new_profile_name.is_empty() => {
+                self.create_profile();
+            }
+            MenuEvent::Activate(MenuAction::SelectProfile(index)) => {
+                self.profiles.select(index);
+                self.save_profiles();
+            }
+            MenuEvent::Activate(MenuAction::SkipName) => self.show_results(),
+            MenuEvent::Back if self.menu_stack.current() == MenuState::EnteringName => self.show_results(),
+            MenuEvent::Back if self.menu_stack.current() == MenuState::GameOver => {
+                self.menu_stack.reset();
+                self.state = GameState::Menu;
+            }
+            MenuEvent::Text(MenuAction::PlayerName | MenuAction::NewProfile, name) => self.new_profile_name = name,
+            MenuEvent::Choose(action, index) => {
+                match action {
+                    MenuAction::ScoreDifficulty => self.score_filter.difficulty = Difficulty::ALL[index],
+                    MenuAction::ScoreMode => self.score_filter.mode = GameMode::ALL[index],
+                    MenuAction::ScorePeriod => self.score_filter.period = Period::ALL[index],
+                    MenuAction::ScoreView => self.score_filter.personal_bests = index == 1,
+                    _ => {}
+                }
+                self.menu_stack.frame_mut().scroll_to_top();
+            }
+            MenuEvent::Toggle(MenuAction::Sound, enabled) => {
+                self.settings.sound_enabled = enabled;
+                self.save_settings().unwrap_or_else(|e| eprintln!("Failed to save settings: {}", e));
+            }
+            MenuEvent::Toggle(MenuAction::Portals, enabled) => {
+                self.settings.portals = enabled;
+                self.save_settings().unwrap_or_else(|e| eprintln!("Failed to save settings: {}", e));
+            }
+            MenuEvent::Toggle(MenuAction::MovingBlocks, enabled) => {
+                self.settings.moving_blocks = enabled;
+                self.save_settings().unwrap_or_else(|e| eprintln!("Failed to save settings: {}", e));
+            }
+            // Values are snapped to the slider's steps so the same settings always land in the same table
+            MenuEvent::Slide(action @ (MenuAction::CustomStartSpeed
+                | MenuAction::CustomAcceleration
+                | MenuAction::CustomTopSpeed
+                | MenuAction::CustomGrowth
+                | MenuAction::CustomMultiplier), value) => {
+                let custom = &mut self.settings.custom_difficulty;
+                match action {
+                    MenuAction::CustomStartSpeed => custom.start_cooldown = 1.0 / value.round(),
+                    MenuAction::CustomAcceleration => custom.acceleration = value.round() / 100.0,
+                    MenuAction::CustomTopSpeed => custom.min_cooldown = 1.0 / value.round(),
+                    MenuAction::CustomGrowth => custom.growth = value.round() as usize,
+                    _ => custom.score_multiplier = (value * 2.0).round() / 2.0,
+                }
+                self.save_settings().unwrap_or_else(|e| eprintln!("Failed to save settings: {}", e));
+            }
+            MenuEvent::Slide(MenuAction::FoodCount, count) => {
+                self.settings.food_count = count.round() as usize;
+                self.save_settings().unwrap_or_else(|e| eprintln!("Failed to save settings: {}", e));
+            }
+            MenuEvent::Slide(MenuAction::Volume, volume) => {
+                self.settings.volume = volume;
+                self.eat_sound.set_volume(volume);
+                self.game_over_sound.set_volume(volume);
+                self.save_settings().unwrap_or_else(|e| eprintln!("Failed to save settings: {}", e));
+            }
+            MenuEvent::Back => {
+                self.menu_stack.pop();
+            }
+            _ => {}
+        }
+    }
 
-    Ok(())
-}
-fn draw_high_scores(&self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
-    let mut title_text = graphics::Text::new("High Scores");
-    let title = title_text.set_scale(40.0);
-    canvas.draw(
-        title,  // No need for & as set_scale returns &mut Text
-        graphics::DrawParam::default()
-            .dest(Point2 {
-                x: (SCREEN_SIZE as f32 / 2.0) - 100.0,
-                y: 50.0,
-            })
-            .color(graphics::Color::WHITE),
-    );
-
-    let difficulties = [
-        (Difficulty::Easy, "Easy"),
-        (Difficulty::Medium, "Medium"),
-        (Difficulty::Hard, "Hard"),
-        (Difficulty::Expert, "Expert"),
-    ];
-
-    for (i, (diff, name)) in difficulties.iter().enumerate() {
-        let diff_scores: Vec<_> = self.high_scores.iter()
-            .filter(|score| score.difficulty == *diff)
-            .take(MAX_SCORES_PER_DIFFICULTY)
-            .collect();
+    fn create_profile(&mut self) {
+        let name = std::mem::take(&mut self.new_profile_name);
+        self.profiles.create(&name);
+        if let (Some(run), Some(profile)) = (self.unrecorded_run.take(), self.profiles.active_mut()) {
+            profile.record(&run);
+        }
+        self.save_profiles();
+    }
 
-        let mut header_text = graphics::Text::new(format!("--- {} ---", name));
-        let header = header_text.set_scale(24.0);
-        canvas.draw(
-            header,  // No need for & as set_scale returns &mut Text
-            graphics::DrawParam::default()
-                .dest(Point2 {
-                    x: 50.0,
-                    y: 120.0 + (i as f32 * 120.0),
-                })
-                .color(graphics::Color::YELLOW),
-        );
+    // Shows the name prompt only when the score earns a place in the table
+    fn end_run(&mut self, death_cause: DeathCause) {
+        let run = RunStats {
+            score: self.score,
+            difficulty: self.difficulty,
+            mode: self.mode,
+            food_eaten: self.food_eaten,
+            snake_length: self.snake.len(),
+            time_played: self.time_played,
+            death_cause,
+        };
+        self.death_cause = Some(death_cause);
+        match self.profiles.active_mut() {
+            Some(profile) => {
+                profile.record(&run);
+                self.save_profiles();
+            }
+            None => self.unrecorded_run = Some(run),
+        }
 
-        for (j, score) in diff_scores.iter().enumerate() {
-            let mut score_text = graphics::Text::new(format!(
-                "{:2}. {:8} {:6} {}",
-                j + 1,
-                score.player_name,
-                score.score,
-                score.timestamp.format("%Y-%m-%d %H:%M"),
-            ));
-            let score_text = score_text.set_scale(20.0);
-            canvas.draw(
-                score_text,  // No need for & as set_scale returns &mut Text
-                graphics::DrawParam::default()
-                    .dest(Point2 {
-                        x: 50.0,
-                        y: 150.0 + (i as f32 * 120.0) + (j as f32 * 25.0),
-                    })
-                    .color(graphics::Color::WHITE),
-            );
+        self.state = GameState::GameOver;
+        self.high_score = self.high_score.max(self.score);
+        self.submitted_banner = None;
+        self.menu_stack.reset();
+        if self.mode == GameMode::Daily {
+            // Recorded straight away so an official attempt can't be skipped and retried
+            self.save_score();
+            self.show_results();
+        } else if self.qualifies(self.score, self.difficulty, self.mode) {
+            self.menu_stack.push(MenuState::EnteringName);
+        } else {
+            self.show_results();
         }
     }
 
-    let mut back_text = graphics::Text::new("Press ESC to return");
-    let back_text = back_text.set_scale(20.0);
-    canvas.draw(
-        back_text,  // No need for & as set_scale returns &mut Text
-        graphics::DrawParam::default()
-            .dest(Point2 {
-                x: (SCREEN_SIZE as f32 / 2.0) - 80.0,
-                y: SCREEN_SIZE as f32 - 50.0,
-            })
-            .color(graphics::Color::YELLOW),
-    );
+    // Runs are submitted once the name is settled, right before the results screen
+    fn show_results(&mut self) {
+        self.submit_online();
+        self.menu_stack.replace(MenuState::GameOver);
+    }
 
-    Ok(())
-}
+    // Posts the run to the shared leaderboard on a background thread; `update` picks up the answer
+    fn submit_online(&mut self) {
+        self.leaderboard_status = None;
+        let Some(server) = self.leaderboard_url.clone() else {
+            return;
+        };
+        // Practice daily runs would crowd out the official attempts
+        if self.score == 0 || (self.mode == GameMode::Daily && !self.daily_official) {
+            return;
+        }
+
+        let player_name = match self.player_name() {
+            "" => GUEST_NAME,
+            name => name,
+        };
+        let submission = Submission {
+            entry: ScoreEntry {
+                player_name: player_name.to_string(),
+                score: self.score,
+                difficulty: self.difficulty,
+                mode: self.mode,
+                timestamp: Local::now(),
+                custom_key: self.custom_key(),
+                adaptive_level: self.adaptive_level(),
+            },
+            replay: self.replay.clone(),
+        };
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            // The game may have moved on by the time this lands; nothing to do if so
+            let _ = sender.send(leaderboard::submit(&server, &submission));
+        });
+        self.pending_submission = Some(receiver);
+        self.leaderboard_status = Some("Submitting to the online leaderboard...".to_string());
+    }
 
+    fn poll_submission(&mut self) {
+        let Some(receiver) = &self.pending_submission else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("submission was dropped".to_string()),
+        };
+        self.pending_submission = None;
+        self.leaderboard_status = Some(match result {
+            Ok(response) => format!("Online rank #{}", response.rank),
+            Err(e) => {
+                eprintln!("Failed to submit score to the leaderboard: {}", e);
+                "Couldn't reach the online leaderboard".to_string()
+            }
+        });
+    }
 
+    fn start_run(&mut self, mode: GameMode, gamepad: Option<GamepadId>) {
+        self.mode = mode;
+        self.difficulty = match mode {
+            GameMode::Daily => DAILY_DIFFICULTY,
+            _ => self.selected_difficulty,
+        };
+        self.custom = self.settings.custom_difficulty;
+        self.initial_cooldown = self.difficulty_info().speed;
+        if mode == GameMode::Daily {
+            self.daily_date = Local::now().date_naive();
+            self.daily_official = self.daily_attempt_open();
+        }
+        self.reset();
+        self.active_gamepad = gamepad;
+        self.state = GameState::Playing;
+    }
 
+    fn pause(&mut self) {
+        self.menu_stack.reset();
+        self.menu_stack.push(MenuState::Pause);
+        self.state = GameState::Paused;
+    }
 
+    fn start_countdown(&mut self) {
+        self.resume_countdown = RESUME_COUNTDOWN;
+        self.state = GameState::Playing;
+    }
 
     fn reset(&mut self) {
+        // Each run gets its own seed so it can be replayed from the recorded turns;
+        // daily runs all share the day's seed
+        let seed = match self.mode {
+            GameMode::Daily => daily::seed_for(self.daily_date),
+            _ => self.rng.gen(),
+        };
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.run_food_count = self.food_count();
+        let mut replay = Replay::new(seed, self.difficulty, self.mode);
+        replay.custom = (self.difficulty == Difficulty::Custom).then_some(self.custom);
+        replay.food_count = self.run_food_count;
+        self.random_portals = self.mode == GameMode::Classic && self.settings.portals;
+        replay.portals = self.random_portals;
+        let moving_blocks = self.mode == GameMode::Classic && self.settings.moving_blocks;
+        replay.moving_blocks = moving_blocks;
+        self.replay = Some(replay);
+        self.walls = match self.mode {
+            GameMode::Daily => self.generate_walls(),
+            _ => Vec::new(),
+        };
+
+        self.arena_margin = 0;
+        self.next_shrink = SURVIVAL_SHRINK_INTERVAL;
+        self.power_ups.clear();
+        self.effects.clear();
+        self.hazards.clear();
+        self.reversed_ticks = 0;
         self.snake.clear();
         // Initialize snake at the center
         for i in 0..3 {
@@ -399,107 +1266,495 @@ fn draw_high_scores(&self, _ctx: &mut Context, canvas: &mut graphics::Canvas) ->
                 y: GRID_SIZE / 2,
             });
         }
-        self.spawn_food();
+        // The daily board always has a portal pair; classic runs only when turned on in settings
+        self.portals.clear();
+        if self.mode == GameMode::Daily || self.random_portals {
+            self.place_portals();
+        }
+        self.entities.clear();
+        if moving_blocks {
+            self.place_patrollers();
+        }
+        self.food.clear();
+        self.pending_growth = 0;
+        self.refill_food();
         self.direction = Direction::Right;
-        self.next_direction = Direction::Right;
+        self.direction_queue.clear();
+        self.stalled = false;
         self.score = 0;
-        self.movement_cooldown = self.initial_cooldown;
+        let recent_scores = self.profiles.active().map_or(&[][..], |profile| &profile.recent_scores[..]);
+        self.adaptive = Adaptive::from_recent(recent_scores);
+        self.movement_cooldown = if self.adaptive_active() { self.adaptive.cooldown() } else { self.initial_cooldown };
         self.particle_effects.clear();
+        self.resume_countdown = 0.0;
+        self.food_eaten = 0;
+        self.time_played = 0.0;
+        self.time_left = TIME_ATTACK_SECONDS;
+        self.unrecorded_run = None;
     }
 
-    fn spawn_food(&mut self) {
-        let mut rng = rand::thread_rng();
-        loop {
-            let pos = Position {
-                x: rng.gen_range(0..GRID_SIZE),
-                y: rng.gen_range(0..GRID_SIZE),
+    fn queue_direction(&mut self, direction: Direction) {
+        let direction = if self.reversed_ticks > 0 { direction.opposite() } else { direction };
+        // Validate against the last queued turn so quick U-turns (e.g. Up then Left) aren't dropped
+        let last = self.direction_queue.back().copied().unwrap_or(self.direction);
+        if direction == last || direction == last.opposite() {
+            return;
+        }
+        if self.direction_queue.len() < MAX_QUEUED_TURNS {
+            self.direction_queue.push_back(direction);
+        }
+    }
+
+    // The daily board's layout: a few short walls placed from the day's seed
+    fn generate_walls(&mut self) -> Vec<Position> {
+        let centre = GRID_SIZE / 2;
+        let mut walls = Vec::new();
+        for _ in 0..self.rng.gen_range(3..=6) {
+            let horizontal = self.rng.gen_bool(0.5);
+            let length: i16 = self.rng.gen_range(3..=7);
+            let start = Position {
+                x: self.rng.gen_range(1..GRID_SIZE - length),
+                y: self.rng.gen_range(1..GRID_SIZE - length),
             };
-            if !self.snake.contains(&pos) {
-                self.food = pos;
-                break;
+            for i in 0..length {
+                let pos = if horizontal {
+                    Position { x: start.x + i, y: start.y }
+                } else {
+                    Position { x: start.x, y: start.y + i }
+                };
+                // Keep the snake's starting row and the stretch ahead of it open
+                if (pos.y - centre).abs() <= 2 && (pos.x - centre).abs() <= 8 {
+                    continue;
+                }
+                walls.push(pos);
             }
         }
+        walls
     }
 
-fn draw_menu(&mut self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
-        // Create mutable Text objects
-        let mut title_text = graphics::Text::new("SNAKE GAME");
-        let title = title_text.set_scale(48.0);
-        
-        let menu_items = [
-            "Play Game",
-            "Difficulty",
-            "High Scores",
-            "Exit",
-        ];
-
-        // Draw title
-        canvas.draw(
-            title,
-            graphics::DrawParam::default()
-                .dest(Point2 {
-                    x: (SCREEN_SIZE as f32 / 2.0) - 100.0,
-                    y: 100.0,
-                })
-                .color(graphics::Color::WHITE),
-        );
+    fn cell_taken(&self, pos: Position) -> bool {
+        self.snake.contains(&pos)
+            || self.walls.contains(&pos)
+            || self.food.iter().any(|item| item.position == pos)
+            || self.power_ups.iter().any(|power_up| power_up.position == pos)
+            || self.hazards.iter().any(|hazard| hazard.position == pos)
+            || self.portals.iter().any(|pair| pair.contains(pos))
+            || self.entities.iter().any(|entity| entity.position == pos)
+    }
 
-        // Draw menu items
-        for (i, item) in menu_items.iter().enumerate() {
-            let color = if i == self.menu_selection {
-                graphics::Color::GREEN
-            } else {
-                graphics::Color::WHITE
-            };
+    fn cell_free(&self, pos: Position) -> bool {
+        let on_board = pos.x >= 0 && pos.x < GRID_SIZE && pos.y >= 0 && pos.y < GRID_SIZE;
+        on_board && !self.cell_taken(pos)
+    }
 
-            let mut menu_text = graphics::Text::new(*item);
-            let text = menu_text.set_scale(32.0);
+    // Only looks inside the arena, which is the whole board outside Survival. A long snake
+    // can fill a shrunken arena, so there may be nowhere left.
+    fn random_free_cell(&mut self) -> Option<Position> {
+        self.random_free_cell_where(|_| true)
+    }
 
-            canvas.draw(
-                text,
-                graphics::DrawParam::default()
-                    .dest(Point2 {
-                        x: (SCREEN_SIZE as f32 / 2.0) - 50.0,
-                        y: 250.0 + (i as f32 * 50.0),
-                    })
-                    .color(color),
-            );
+    fn random_free_cell_where(&mut self, allowed: impl Fn(Position) -> bool) -> Option<Position> {
+        let (low, high) = (self.arena_margin, GRID_SIZE - self.arena_margin);
+        let free: Vec<Position> = (low..high)
+            .flat_map(|y| (low..high).map(move |x| Position { x, y }))
+            .filter(|&pos| !self.cell_taken(pos) && allowed(pos))
+            .collect();
+        if free.is_empty() {
+            return None;
         }
+        Some(free[self.rng.gen_range(0..free.len())])
+    }
 
-        Ok(())
+    // Returns false when there was no room for it
+    fn spawn_food(&mut self, kind: FoodKind) -> bool {
+        let Some(position) = self.random_free_cell() else {
+            return false;
+        };
+        self.food.push(Food::new(kind, position));
+        true
     }
-    fn draw_game(&mut self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
-        // Draw grid
-        for i in 0..GRID_SIZE {
-            for j in 0..GRID_SIZE {
-                if (i + j) % 2 == 0 {
-                    let rect = graphics::Rect::new(
-                        (i * GRID_CELL_SIZE) as f32,
-                        (j * GRID_CELL_SIZE) as f32,
-                        GRID_CELL_SIZE as f32,
-                        GRID_CELL_SIZE as f32,
-                    );
-                    canvas.draw(
-                        &graphics::Mesh::new_rectangle(
-                            ctx,
-                            graphics::DrawMode::fill(),
-                            rect,
-                            GRID_COLOR,
-                        )?,
-                        graphics::DrawParam::default(),
-                    );
-                }
-            }
+
+    // Golden food only lasts a few moves, so it turns up where the head can still get to it in time
+    fn spawn_golden_food(&mut self, head: Position) {
+        let lifetime = FoodKind::Golden.info().lifetime.unwrap_or(0);
+        let reach = lifetime.saturating_sub(food::GOLDEN_REACH_MARGIN) as i16;
+        let near = |pos: Position| (pos.x - head.x).abs() + (pos.y - head.y).abs() <= reach;
+        if let Some(position) = self.random_free_cell_where(near) {
+            self.food.push(Food::new(FoodKind::Golden, position));
         }
+    }
 
-        // Draw snake with gradient effect
-        for (i, pos) in self.snake.iter().enumerate() {
-            let progress = i as f32 / self.snake.len() as f32;
-            let color = graphics::Color::new(
+    // Daily runs always have a single food so everyone's board matches
+    fn food_count(&self) -> usize {
+        match self.mode {
+            GameMode::Daily => 1,
+            _ => self.settings.food_count.clamp(1, MAX_FOOD_COUNT),
+        }
+    }
+
+    // Tops the board back up with regular or big food; golden bonuses don't count towards it
+    fn refill_food(&mut self) {
+        while self.food.iter().filter(|item| item.kind != FoodKind::Golden).count() < self.run_food_count {
+            let kind = if self.rng.gen_bool(food::BIG_FOOD_CHANCE) { FoodKind::Big } else { FoodKind::Regular };
+            if !self.spawn_food(kind) {
+                break;
+            }
+        }
+    }
+
+    // Rolled each time food is eaten; at most one new pickup per roll
+    fn maybe_spawn_power_up(&mut self) {
+        for kind in PowerUpKind::ALL {
+            let info = kind.info();
+            if self.rng.gen_bool(info.spawn_chance) {
+                if let Some(position) = self.random_free_cell() {
+                    self.power_ups.push(PowerUp {
+                        kind,
+                        position,
+                        ticks_left: info.lifetime,
+                    });
+                }
+                return;
+            }
+        }
+    }
+
+    // Replaces any existing portals with a fresh pair spaced well apart
+    fn place_portals(&mut self) {
+        self.portals.clear();
+        let Some(a) = self.random_free_cell() else {
+            return;
+        };
+        if let Some(b) = self.random_free_cell_where(|b| (a.x - b.x).abs() + (a.y - b.y).abs() >= PORTAL_MIN_DISTANCE) {
+            self.portals.push(PortalPair { ends: [a, b] });
+        }
+    }
+
+    // Walls off the outermost open ring, clearing anything on it. Returns true if the snake was caught in it.
+    fn shrink_arena(&mut self) -> bool {
+        let ring = ring_cells(self.arena_margin);
+        self.arena_margin += 1;
+        let crushed = self.snake.iter().any(|segment| ring.contains(segment));
+        self.food.retain(|item| !ring.contains(&item.position));
+        self.power_ups.retain(|power_up| !ring.contains(&power_up.position));
+        self.hazards.retain(|hazard| !ring.contains(&hazard.position));
+        self.entities.retain(|entity| !ring.contains(&entity.position));
+        self.portals.retain(|pair| !pair.ends.iter().any(|end| ring.contains(end)));
+        self.walls.extend(ring);
+        if !crushed {
+            self.refill_food();
+        }
+        crushed
+    }
+
+    // Zen's stand-in for dying: lose a few segments and stop until the player turns
+    fn zen_bump(&mut self) {
+        let keep = self.snake.len().saturating_sub(ZEN_PENALTY_SEGMENTS).max(MIN_SNAKE_LENGTH);
+        self.snake.truncate(keep);
+        self.direction_queue.clear();
+        self.stalled = true;
+    }
+
+    fn adjust_zen_speed(&mut self, factor: f32) {
+        self.movement_cooldown = (self.movement_cooldown * factor).clamp(ZEN_MIN_COOLDOWN, ZEN_MAX_COOLDOWN);
+    }
+
+    fn arena_can_shrink(&self) -> bool {
+        self.mode == GameMode::Survival && GRID_SIZE - 2 * self.arena_margin > SURVIVAL_MIN_ARENA
+    }
+
+    // Patrollers start off the snake's row so the opening moves are safe
+    fn place_patrollers(&mut self) {
+        while self.entities.len() < entity::PATROLLER_COUNT {
+            let Some(position) = self.random_free_cell_where(|pos| (pos.y - GRID_SIZE / 2).abs() > 2) else {
+                return;
+            };
+            let direction = [Direction::Up, Direction::Down, Direction::Left, Direction::Right][self.rng.gen_range(0..4)];
+            self.entities.push(Entity::new(EntityKind::Patroller, position, direction));
+        }
+    }
+
+    fn maybe_spawn_mouse(&mut self) {
+        let mouse_on_board = self.entities.iter().any(|entity| entity.kind == EntityKind::Mouse);
+        if mouse_on_board || !self.rng.gen_bool(entity::MOUSE_CHANCE) {
+            return;
+        }
+        if let Some(position) = self.random_free_cell() {
+            self.entities.push(Entity::new(EntityKind::Mouse, position, Direction::Up));
+        }
+    }
+
+    // Patrollers go back and forth, turning round at anything in the way; the mouse runs
+    // from the head when it's close and wanders otherwise. Neither moves onto the snake.
+    fn move_entities(&mut self) {
+        let head = self.snake[0];
+        for i in 0..self.entities.len() {
+            if !self.entities[i].ready() {
+                continue;
+            }
+            let current = self.entities[i];
+            let target = match current.kind {
+                EntityKind::Patroller => {
+                    let ahead = entity::step(current.position, current.direction);
+                    if self.cell_free(ahead) {
+                        Some(ahead)
+                    } else {
+                        let direction = current.direction.opposite();
+                        self.entities[i].direction = direction;
+                        Some(entity::step(current.position, direction)).filter(|&back| self.cell_free(back))
+                    }
+                }
+                EntityKind::Mouse => self.mouse_step(current.position, head),
+            };
+            if let Some(target) = target {
+                self.entities[i].position = target;
+            }
+        }
+    }
+
+    fn mouse_step(&mut self, pos: Position, head: Position) -> Option<Position> {
+        let distance = |a: Position| (a.x - head.x).abs() + (a.y - head.y).abs();
+        let options: Vec<Position> = [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+            .into_iter()
+            .map(|direction| entity::step(pos, direction))
+            .filter(|&cell| self.cell_free(cell))
+            .collect();
+        if options.is_empty() {
+            return None;
+        }
+        if distance(pos) > entity::MOUSE_SENSE_RADIUS {
+            return Some(options[self.rng.gen_range(0..options.len())]);
+        }
+        options.into_iter().filter(|&cell| distance(cell) > distance(pos)).max_by_key(|&cell| distance(cell))
+    }
+
+    // Rolled each time food is eaten, more likely the harder the difficulty
+    fn maybe_spawn_hazard(&mut self) {
+        if self.hazards.len() >= hazard::MAX_HAZARDS || !self.rng.gen_bool(self.difficulty_info().hazard_chance) {
+            return;
+        }
+        let kind = HazardKind::ALL[self.rng.gen_range(0..HazardKind::ALL.len())];
+        if let Some(position) = self.random_free_cell() {
+            self.hazards.push(Hazard::new(kind, position));
+        }
+    }
+
+    // Returns the death cause if the hazard ends the run
+    fn hit_hazard(&mut self, kind: HazardKind) -> Option<DeathCause> {
+        match kind {
+            HazardKind::Poison => {
+                let keep = self.snake.len().saturating_sub(hazard::POISON_SEGMENTS).max(MIN_SNAKE_LENGTH);
+                self.snake.truncate(keep);
+                self.score = self.score.saturating_sub(hazard::POISON_PENALTY);
+            }
+            HazardKind::Bomb => return Some(DeathCause::Bomb),
+            HazardKind::Rotten => {
+                self.reversed_ticks = hazard::ROTTEN_DURATION;
+                // Turns already queued were pressed before the swap
+                self.direction_queue.clear();
+            }
+        }
+        None
+    }
+
+    fn difficulty_info(&self) -> DifficultyInfo {
+        match self.difficulty {
+            Difficulty::Custom => self.custom.info(),
+            Difficulty::Adaptive => self.adaptive.info(),
+            diff => diff.get_info(),
+        }
+    }
+
+    // Zen keeps its player-set pace even on Adaptive
+    fn adaptive_active(&self) -> bool {
+        self.difficulty == Difficulty::Adaptive && self.mode != GameMode::Zen
+    }
+
+    fn adaptive_level(&self) -> Option<f32> {
+        self.adaptive_active().then_some(self.adaptive.level)
+    }
+
+    // Whether moving into `pos` would end the run
+    fn deadly(&self, pos: Position) -> bool {
+        let off_board = pos.x < 0 || pos.x >= GRID_SIZE || pos.y < 0 || pos.y >= GRID_SIZE;
+        off_board
+            || self.walls.contains(&pos)
+            || self.snake[..self.snake.len() - 1].contains(&pos)
+            || self.entities.iter().any(|entity| entity.kind == EntityKind::Patroller && entity.position == pos)
+    }
+
+    // Custom runs each have a table per set of settings
+    fn custom_key(&self) -> Option<u64> {
+        (self.difficulty == Difficulty::Custom).then(|| self.custom.key())
+    }
+
+    // Points double while the doubler is running. Only Custom and Adaptive apply their multiplier:
+    // the standard tables have always been scored without one.
+    fn award(&mut self, points: u32) {
+        let doubler = if self.effect_active(PowerUpKind::ScoreDoubler) { 2.0 } else { 1.0 };
+        let multiplier = match self.difficulty {
+            Difficulty::Custom | Difficulty::Adaptive => self.difficulty_info().score_multiplier,
+            _ => 1.0,
+        };
+        self.score += (points as f32 * multiplier * doubler).round() as u32;
+    }
+
+    fn effect_active(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    fn collect_power_up(&mut self, kind: PowerUpKind) {
+        if kind == PowerUpKind::Shrink {
+            let keep = self.snake.len().saturating_sub(SHRINK_SEGMENTS).max(MIN_SNAKE_LENGTH);
+            self.snake.truncate(keep);
+        }
+        powerup::activate(&mut self.effects, kind);
+    }
+
+    // Moves each nearby food one step towards the head, unless something's in the way
+    fn pull_food(&mut self, head: Position) {
+        for i in 0..self.food.len() {
+            let pos = self.food[i].position;
+            let (dx, dy) = (head.x - pos.x, head.y - pos.y);
+            if dx.abs() + dy.abs() > MAGNET_RADIUS {
+                continue;
+            }
+            let target = if dx.abs() >= dy.abs() {
+                Position { x: pos.x + dx.signum(), y: pos.y }
+            } else {
+                Position { x: pos.x, y: pos.y + dy.signum() }
+            };
+            let blocked = target != head && self.cell_taken(target);
+            if !blocked {
+                self.food[i].position = target;
+            }
+        }
+    }
+
+    // Seconds between moves, stretched while slow-motion is running
+    fn tick_interval(&self) -> f32 {
+        if self.effect_active(PowerUpKind::SlowMotion) {
+            self.movement_cooldown * SLOW_MOTION_FACTOR
+        } else {
+            self.movement_cooldown
+        }
+    }
+
+    fn draw_game(&mut self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        // Draw grid
+        for i in 0..GRID_SIZE {
+            for j in 0..GRID_SIZE {
+                if (i + j) % 2 == 0 {
+                    let rect = graphics::Rect::new(
+                        (i * GRID_CELL_SIZE) as f32,
+                        (j * GRID_CELL_SIZE) as f32,
+                        GRID_CELL_SIZE as f32,
+                        GRID_CELL_SIZE as f32,
+                    );
+                    canvas.draw(
+                        &graphics::Mesh::new_rectangle(
+                            ctx,
+                            graphics::DrawMode::fill(),
+                            rect,
+                            GRID_COLOR,
+                        )?,
+                        graphics::DrawParam::default(),
+                    );
+                }
+            }
+        }
+
+        for wall in &self.walls {
+            let rect = graphics::Rect::new(
+                (wall.x * GRID_CELL_SIZE) as f32,
+                (wall.y * GRID_CELL_SIZE) as f32,
+                GRID_CELL_SIZE as f32,
+                GRID_CELL_SIZE as f32,
+            );
+            canvas.draw(
+                &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), rect, WALL_COLOR)?,
+                graphics::DrawParam::default(),
+            );
+        }
+
+        // The ring about to close in Survival flashes as a warning
+        if self.arena_can_shrink() && self.next_shrink < SURVIVAL_SHRINK_WARNING && (self.next_shrink * 4.0) as i32 % 2 == 0 {
+            let warning = graphics::Color::new(WALL_COLOR.r, WALL_COLOR.g, WALL_COLOR.b, 0.4);
+            for cell in ring_cells(self.arena_margin) {
+                let rect = graphics::Rect::new(
+                    (cell.x * GRID_CELL_SIZE) as f32,
+                    (cell.y * GRID_CELL_SIZE) as f32,
+                    GRID_CELL_SIZE as f32,
+                    GRID_CELL_SIZE as f32,
+                );
+                canvas.draw(
+                    &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), rect, warning)?,
+                    graphics::DrawParam::default(),
+                );
+            }
+        }
+
+        // Portals are drawn as pulsing rings, each pair in its own colour
+        let ring = 0.3 + (self.food_animation * 2.0 * PI).sin().abs() * 0.1;
+        for (i, pair) in self.portals.iter().enumerate() {
+            let color = portal::PORTAL_COLORS[i % portal::PORTAL_COLORS.len()];
+            for end in pair.ends {
+                let centre = Point2 {
+                    x: (end.x * GRID_CELL_SIZE) as f32 + GRID_CELL_SIZE as f32 / 2.0,
+                    y: (end.y * GRID_CELL_SIZE) as f32 + GRID_CELL_SIZE as f32 / 2.0,
+                };
+                for radius in [0.45, ring] {
+                    canvas.draw(
+                        &graphics::Mesh::new_circle(ctx, graphics::DrawMode::stroke(2.0), centre, GRID_CELL_SIZE as f32 * radius, 0.5, color)?,
+                        graphics::DrawParam::default(),
+                    );
+                }
+            }
+        }
+
+        // Patrollers are blocks with a dark core; the mouse is a small round body with ears
+        for entity in &self.entities {
+            if entity.ticks_left.is_some_and(|ticks_left| ticks_left < BLINK_TICKS && ticks_left % 2 == 0) {
+                continue;
+            }
+            let color = entity.kind.info().color;
+            let cell = GRID_CELL_SIZE as f32;
+            let (left, top) = ((entity.position.x * GRID_CELL_SIZE) as f32, (entity.position.y * GRID_CELL_SIZE) as f32);
+            match entity.kind {
+                EntityKind::Patroller => {
+                    canvas.draw(
+                        &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), graphics::Rect::new(left + 1.0, top + 1.0, cell - 2.0, cell - 2.0), color)?,
+                        graphics::DrawParam::default(),
+                    );
+                    canvas.draw(
+                        &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), graphics::Rect::new(left + 6.0, top + 6.0, cell - 12.0, cell - 12.0), BACKGROUND_COLOR)?,
+                        graphics::DrawParam::default(),
+                    );
+                }
+                EntityKind::Mouse => {
+                    canvas.draw(
+                        &graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), Point2 { x: left + cell / 2.0, y: top + cell * 0.6 }, cell * 0.35, 0.5, color)?,
+                        graphics::DrawParam::default(),
+                    );
+                    for ear_x in [0.25, 0.75] {
+                        canvas.draw(
+                            &graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), Point2 { x: left + cell * ear_x, y: top + cell * 0.25 }, cell * 0.15, 0.5, color)?,
+                            graphics::DrawParam::default(),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Draw snake with gradient effect, see-through while it's a ghost
+        let snake_alpha = if self.effect_active(PowerUpKind::Ghost) { 0.45 } else { 1.0 };
+        for (i, pos) in self.snake.iter().enumerate() {
+            let progress = i as f32 / self.snake.len() as f32;
+            let color = graphics::Color::new(
                 0.0,
                 0.8 + progress * 0.2,
                 0.0,
-                1.0,
+                snake_alpha,
             );
 
             let rect = graphics::Rect::new(
@@ -519,27 +1774,90 @@ fn draw_menu(&mut self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> Ga
             );
         }
 
-        // Draw animated food
+        // Draw animated food, blinking when it's about to disappear
         let food_scale = 1.0 + (self.food_animation * PI).sin() * 0.2;
-        let food_color_index = ((self.food_animation * 5.0) as usize) % FOOD_COLORS.len();
-        let food_size = GRID_CELL_SIZE as f32 * food_scale;
-        let food_offset = (GRID_CELL_SIZE as f32 - food_size) / 2.0;
-
-        let food_rect = graphics::Rect::new(
-            (self.food.x * GRID_CELL_SIZE) as f32 + food_offset,
-            (self.food.y * GRID_CELL_SIZE) as f32 + food_offset,
-            food_size,
-            food_size,
-        );
-        canvas.draw(
-            &graphics::Mesh::new_rectangle(
-                ctx,
-                graphics::DrawMode::fill(),
-                food_rect,
-                FOOD_COLORS[food_color_index],
-            )?,
-            graphics::DrawParam::default(),
-        );
+        for item in &self.food {
+            if item.ticks_left.is_some_and(|ticks_left| ticks_left < BLINK_TICKS && ticks_left % 2 == 0) {
+                continue;
+            }
+            let info = item.kind.info();
+            let food_color_index = ((self.food_animation * 5.0) as usize) % info.palette.len();
+            let food_size = GRID_CELL_SIZE as f32 * food_scale * info.size;
+            let food_offset = (GRID_CELL_SIZE as f32 - food_size) / 2.0;
+
+            let food_rect = graphics::Rect::new(
+                (item.position.x * GRID_CELL_SIZE) as f32 + food_offset,
+                (item.position.y * GRID_CELL_SIZE) as f32 + food_offset,
+                food_size,
+                food_size,
+            );
+            canvas.draw(
+                &graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    food_rect,
+                    info.palette[food_color_index],
+                )?,
+                graphics::DrawParam::default(),
+            );
+        }
+
+        // Power-ups are round so they can't be mistaken for food, and blink before vanishing
+        for power_up in &self.power_ups {
+            if power_up.ticks_left < BLINK_TICKS && power_up.ticks_left % 2 == 0 {
+                continue;
+            }
+            let radius = GRID_CELL_SIZE as f32 / 2.0 * (0.8 + (self.food_animation * 2.0 * PI).sin().abs() * 0.2);
+            let centre = Point2 {
+                x: (power_up.position.x * GRID_CELL_SIZE) as f32 + GRID_CELL_SIZE as f32 / 2.0,
+                y: (power_up.position.y * GRID_CELL_SIZE) as f32 + GRID_CELL_SIZE as f32 / 2.0,
+            };
+            canvas.draw(
+                &graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), centre, radius, 0.5, power_up.kind.info().color)?,
+                graphics::DrawParam::default(),
+            );
+        }
+
+        // Hazards get their own shapes: a poison ring, a bomb with a flashing fuse ring, a rotten square
+        for hazard in &self.hazards {
+            if hazard.ticks_left < BLINK_TICKS && hazard.ticks_left % 2 == 0 {
+                continue;
+            }
+            let color = hazard.kind.info().color;
+            let cell = GRID_CELL_SIZE as f32;
+            let (left, top) = ((hazard.position.x * GRID_CELL_SIZE) as f32, (hazard.position.y * GRID_CELL_SIZE) as f32);
+            let centre = Point2 { x: left + cell / 2.0, y: top + cell / 2.0 };
+            match hazard.kind {
+                HazardKind::Poison => {
+                    canvas.draw(
+                        &graphics::Mesh::new_circle(ctx, graphics::DrawMode::stroke(3.0), centre, cell * 0.35, 0.5, color)?,
+                        graphics::DrawParam::default(),
+                    );
+                    canvas.draw(
+                        &graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), centre, cell * 0.12, 0.5, color)?,
+                        graphics::DrawParam::default(),
+                    );
+                }
+                HazardKind::Bomb => {
+                    let fuse = graphics::Color::new(1.0, 0.1, 0.1, 0.5 + (self.food_animation * 3.0 * PI).sin().abs() * 0.5);
+                    canvas.draw(
+                        &graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), centre, cell * 0.4, 0.5, color)?,
+                        graphics::DrawParam::default(),
+                    );
+                    canvas.draw(
+                        &graphics::Mesh::new_circle(ctx, graphics::DrawMode::stroke(2.0), centre, cell * 0.45, 0.5, fuse)?,
+                        graphics::DrawParam::default(),
+                    );
+                }
+                HazardKind::Rotten => {
+                    let rect = graphics::Rect::new(left + 3.0, top + 3.0, cell - 6.0, cell - 6.0);
+                    canvas.draw(
+                        &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), rect, color)?,
+                        graphics::DrawParam::default(),
+                    );
+                }
+            }
+        }
 
         // Draw particle effects
         for effect in &self.particle_effects {
@@ -563,12 +1881,34 @@ fn draw_menu(&mut self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> Ga
         }
 
         // Draw UI
+        let player_label = match self.active_gamepad {
+            Some(id) => match self.gamepad_players.iter().position(|&pad| pad == id) {
+                Some(index) => format!(" | P{}", index + 1),
+                None => String::new(),
+            },
+            None => String::new(),
+        };
+        let mode_label = match self.mode {
+            GameMode::Daily if self.daily_official => " | Daily".to_string(),
+            GameMode::Daily => " | Daily (practice)".to_string(),
+            GameMode::TimeAttack => format!(" | Time: {:.1}s", self.time_left.max(0.0)),
+            GameMode::Survival if self.arena_can_shrink() => format!(" | Survival: shrinks in {:.0}s", self.next_shrink.ceil()),
+            GameMode::Survival => " | Survival".to_string(),
+            GameMode::Zen => " | Zen (+/- speed)".to_string(),
+            GameMode::Classic => String::new(),
+        };
+        let difficulty_label = match self.difficulty {
+            Difficulty::Adaptive if self.adaptive_active() => format!("Adaptive (level {:.1})", self.adaptive.level),
+            diff => format!("{:?}", diff),
+        };
         let score_text = graphics::Text::new(format!(
-            "Score: {} | High Score: {} | Speed: {:.2} | {:?}",
+            "Score: {} | High Score: {} | Speed: {:.2} | {}{}{}",
             self.score,
             self.high_score,
-            1.0 / self.movement_cooldown,
-            self.difficulty,
+            1.0 / self.tick_interval(),
+            difficulty_label,
+            mode_label,
+            player_label,
         ));
         canvas.draw(
             &score_text,
@@ -577,12 +1917,67 @@ fn draw_menu(&mut self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> Ga
                 .color(graphics::Color::WHITE),
         );
 
+        // Running effects and their timers, each in its pickup's colour
+        let mut timers: Vec<(&str, f32, graphics::Color)> = self.effects.iter()
+            .map(|effect| {
+                let info = effect.kind.info();
+                (info.label, effect.ticks_left as f32 * self.tick_interval(), info.color)
+            })
+            .collect();
+        if self.reversed_ticks > 0 {
+            let info = HazardKind::Rotten.info();
+            timers.push((info.label, self.reversed_ticks as f32 * self.tick_interval(), info.color));
+        }
+        let mut x = 10.0;
+        for (label, time_left, color) in timers {
+            let effect_text = graphics::Text::new(format!("{} {:.1}s", label, time_left));
+            let width = effect_text.measure(ctx)?.x;
+            canvas.draw(
+                &effect_text,
+                graphics::DrawParam::default()
+                    .dest(Point2 { x, y: 30.0 })
+                    .color(color),
+            );
+            x += width + 15.0;
+        }
+
         Ok(())
     }
 
     fn update_game(&mut self, ctx: &mut Context, dt: f32) -> GameResult {
         self.food_animation = (self.food_animation + dt) % (2.0 * PI);
-        
+        self.time_played += dt;
+        if self.adaptive_active() && self.adaptive.tick(dt) {
+            self.movement_cooldown = self.adaptive.cooldown();
+        }
+
+        if self.mode == GameMode::TimeAttack {
+            self.time_left -= dt;
+            if self.time_left <= 0.0 {
+                self.end_run(DeathCause::TimeUp);
+                if self.settings.sound_enabled {
+                    self.game_over_sound.play_detached(ctx)?;
+                }
+                return Ok(());
+            }
+        }
+        if self.arena_can_shrink() {
+            self.next_shrink -= dt;
+            if self.next_shrink <= 0.0 {
+                self.next_shrink = SURVIVAL_SHRINK_INTERVAL;
+                if let Some(replay) = &mut self.replay {
+                    replay.shrinks.push(replay.ticks);
+                }
+                if self.shrink_arena() {
+                    self.end_run(DeathCause::Wall);
+                    if self.settings.sound_enabled {
+                        self.game_over_sound.play_detached(ctx)?;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
         // Update particle effects
         self.particle_effects.retain_mut(|effect| {
             effect.update(dt);
@@ -591,52 +1986,260 @@ fn draw_menu(&mut self, _ctx: &mut Context, canvas: &mut graphics::Canvas) -> Ga
 
         // Update snake movement
         let current_time = ctx.time.time_since_start().as_secs_f32();
-        if current_time - self.last_update >= self.movement_cooldown {
+        if current_time - self.last_update >= self.tick_interval() {
             self.last_update = current_time;
-            self.direction = self.next_direction;
-
-            let head = self.snake.first().unwrap().clone();
-            let new_head = match self.direction {
-                Direction::Up => Position { x: head.x, y: head.y - 1 },
-                Direction::Down => Position { x: head.x, y: head.y + 1 },
-                Direction::Left => Position { x: head.x - 1, y: head.y },
-                Direction::Right => Position { x: head.x + 1, y: head.y },
+            // Consume at most one buffered turn per tick
+            let turn = self.direction_queue.pop_front();
+            if self.stalled && turn.is_none() {
+                return Ok(());
+            }
+            self.stalled = false;
+            if let Some(direction) = turn {
+                // Turning away at the last moment from something that would have ended the run
+                let head = self.snake[0];
+                if self.adaptive_active() && self.deadly(entity::step(head, self.direction)) {
+                    self.adaptive.near_miss();
+                }
+                self.direction = direction;
+            }
+            let adaptive_level = self.adaptive_level();
+            if let Some(replay) = &mut self.replay {
+                if let Some(level) = adaptive_level {
+                    replay.record_level(level);
+                }
+                replay.record_tick(turn);
+            }
+            expiring::tick_timers(&mut self.power_ups);
+            expiring::tick_timers(&mut self.effects);
+            expiring::tick_timers(&mut self.food);
+            expiring::tick_timers(&mut self.hazards);
+            expiring::tick_timers(&mut self.entities);
+            self.reversed_ticks = self.reversed_ticks.saturating_sub(1);
+
+            let new_head = entity::step(self.snake[0], self.direction);
+            // Zen has no edges: the snake comes out on the far side
+            let new_head = if self.mode == GameMode::Zen {
+                Position { x: new_head.x.rem_euclid(GRID_SIZE), y: new_head.y.rem_euclid(GRID_SIZE) }
+            } else {
+                new_head
             };
+            // Stepping into a portal brings the head out of its partner, still heading the same way,
+            // so from here on the head and the segment behind it needn't be neighbours
+            let new_head = portal::exit_for(&self.portals, new_head).unwrap_or(new_head);
 
             // Check collisions
-            if new_head.x < 0 || new_head.x >= GRID_SIZE || new_head.y < 0 || new_head.y >= GRID_SIZE 
-                || self.snake.contains(&new_head) {
-                self.state = GameState::GameOver;
-                self.high_score = self.high_score.max(self.score);
-                self.game_over_sound.play_detached(ctx)?;
+            let off_board = new_head.x < 0 || new_head.x >= GRID_SIZE || new_head.y < 0 || new_head.y >= GRID_SIZE;
+            let death_cause = if off_board || self.walls.contains(&new_head) {
+                Some(DeathCause::Wall)
+            } else if self.entities.iter().any(|entity| entity.kind == EntityKind::Patroller && entity.position == new_head) {
+                Some(DeathCause::Obstacle)
+            } else if self.snake.contains(&new_head) && !self.effect_active(PowerUpKind::Ghost) {
+                Some(DeathCause::SelfCollision)
+            } else {
+                None
+            };
+            if death_cause.is_some() && self.mode == GameMode::Zen {
+                self.zen_bump();
+                return Ok(());
+            }
+            if let Some(death_cause) = death_cause {
+                self.end_run(death_cause);
+                if self.settings.sound_enabled {
+                    self.game_over_sound.play_detached(ctx)?;
+                }
                 return Ok(());
             }
 
             // Move snake
             self.snake.insert(0, new_head);
 
+            if self.effect_active(PowerUpKind::Magnet) {
+                self.pull_food(new_head);
+            }
+
             // Check food collision
-            if new_head == self.food {
-                self.score += 10;
-                self.eat_sound.play_detached(ctx)?;
-                self.particle_effects.push(ParticleEffect::new(self.food));
-                self.spawn_food();
-                // Speed up
-                self.movement_cooldown = (self.movement_cooldown * 0.95).max(0.05);
+            if let Some(index) = self.food.iter().position(|item| item.position == new_head) {
+                let eaten = self.food.remove(index);
+                let info = eaten.kind.info();
+                self.award(info.points);
+                self.food_eaten += 1;
+                self.pending_growth += info.growth * self.difficulty_info().growth;
+                if self.mode == GameMode::TimeAttack && eaten.kind == FoodKind::Golden {
+                    self.time_left += TIME_ATTACK_GOLDEN_BONUS;
+                }
+                if self.random_portals && self.food_eaten.is_multiple_of(portal::RELOCATE_AFTER_FOOD) {
+                    self.place_portals();
+                }
+                if self.settings.sound_enabled {
+                    self.eat_sound.play_detached(ctx)?;
+                }
+                self.particle_effects.push(ParticleEffect::new(eaten.position));
+                self.refill_food();
+                let golden_on_board = self.food.iter().any(|item| item.kind == FoodKind::Golden);
+                if !golden_on_board && self.rng.gen_bool(food::GOLDEN_FOOD_CHANCE) {
+                    self.spawn_golden_food(new_head);
+                }
+                self.maybe_spawn_power_up();
+                self.maybe_spawn_hazard();
+                self.maybe_spawn_mouse();
+                // Speed up, except in Zen where the player sets the pace
+                if self.adaptive_active() {
+                    self.adaptive.on_eat();
+                    self.movement_cooldown = self.adaptive.cooldown();
+                } else if self.mode != GameMode::Zen {
+                    let pace = self.difficulty_info();
+                    let sped_up = (self.movement_cooldown * (1.0 - pace.acceleration)).max(pace.min_cooldown);
+                    // A Custom start faster than its top speed just stays at the start speed
+                    self.movement_cooldown = sped_up.min(self.movement_cooldown);
+                }
+            }
+
+            // The tail stays put while the snake still has growing to do
+            if self.pending_growth > 0 {
+                self.pending_growth -= 1;
             } else {
                 self.snake.pop();
             }
+
+            // After the tail has moved, so losing segments can't take the snake below its minimum length
+            if let Some(index) = self.hazards.iter().position(|hazard| hazard.position == new_head) {
+                let hazard = self.hazards.remove(index);
+                let death_cause = self.hit_hazard(hazard.kind);
+                if death_cause.is_some() && self.mode == GameMode::Zen {
+                    let keep = self.snake.len().saturating_sub(ZEN_PENALTY_SEGMENTS).max(MIN_SNAKE_LENGTH);
+                    self.snake.truncate(keep);
+                } else if let Some(death_cause) = death_cause {
+                    self.end_run(death_cause);
+                    if self.settings.sound_enabled {
+                        self.game_over_sound.play_detached(ctx)?;
+                    }
+                    return Ok(());
+                }
+            }
+
+            if let Some(index) = self.power_ups.iter().position(|power_up| power_up.position == new_head) {
+                let power_up = self.power_ups.remove(index);
+                if self.settings.sound_enabled {
+                    self.eat_sound.play_detached(ctx)?;
+                }
+                self.particle_effects.push(ParticleEffect::new(power_up.position));
+                self.collect_power_up(power_up.kind);
+            }
+
+            // Catching the mouse counts as eating, without the speed-up
+            if let Some(index) = self.entities.iter().position(|entity| entity.kind == EntityKind::Mouse && entity.position == new_head) {
+                let mouse = self.entities.remove(index);
+                self.award(entity::MOUSE_POINTS);
+                self.food_eaten += 1;
+                self.pending_growth += entity::MOUSE_GROWTH * self.difficulty_info().growth;
+                if self.settings.sound_enabled {
+                    self.eat_sound.play_detached(ctx)?;
+                }
+                self.particle_effects.push(ParticleEffect::new(mouse.position));
+            }
+
+            self.move_entities();
         }
 
         Ok(())
     }
+
+    fn gamepad_player(&mut self, id: GamepadId) -> usize {
+        match self.gamepad_players.iter().position(|&pad| pad == id) {
+            Some(index) => index + 1,
+            None => {
+                self.gamepad_players.push(id);
+                self.gamepad_players.len()
+            }
+        }
+    }
+
+    fn gamepad_button_to_key(&self, button: Button) -> Option<KeyCode> {
+        match (self.state, button) {
+            (_, Button::DPadUp) => Some(KeyCode::Up),
+            (_, Button::DPadDown) => Some(KeyCode::Down),
+            (_, Button::DPadLeft) => Some(KeyCode::Left),
+            (_, Button::DPadRight) => Some(KeyCode::Right),
+            (GameState::Playing | GameState::Paused, Button::Start) => Some(KeyCode::Escape),
+            (GameState::Paused, Button::Select) => Some(KeyCode::M),
+            (_, Button::South | Button::Start) => Some(KeyCode::Return),
+            (_, Button::East | Button::Select) => Some(KeyCode::Escape),
+            _ => None,
+        }
+    }
+
+    fn handle_gamepad_key(&mut self, keycode: KeyCode, id: GamepadId) {
+        self.gamepad_player(id);
+        let steering = matches!(keycode, KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right);
+        // Only the pad that started the run may steer it
+        if self.state == GameState::Playing && steering && self.active_gamepad.is_some_and(|pad| pad != id) {
+            return;
+        }
+        self.handle_key(keycode, Some(id));
+    }
+
+    // Shared by keyboard and gamepad input; `gamepad` is the pad that sent the input, if any
+    fn handle_key(&mut self, keycode: KeyCode, gamepad: Option<GamepadId>) {
+        match self.state {
+            GameState::Menu => {
+                let menu = self.build_menu(self.menu_stack.current());
+                if let Some(event) = menu.key(self.menu_stack.frame_mut(), keycode) {
+                    self.apply_menu_event(event, gamepad);
+                }
+            }
+            GameState::Playing => {
+                match keycode {
+                    KeyCode::Up => self.queue_direction(Direction::Up),
+                    KeyCode::Down => self.queue_direction(Direction::Down),
+                    KeyCode::Left => self.queue_direction(Direction::Left),
+                    KeyCode::Right => self.queue_direction(Direction::Right),
+                    KeyCode::Escape => self.pause(),
+                    KeyCode::Equals | KeyCode::Plus | KeyCode::NumpadAdd if self.mode == GameMode::Zen => self.adjust_zen_speed(1.0 / ZEN_SPEED_STEP),
+                    KeyCode::Minus | KeyCode::NumpadSubtract if self.mode == GameMode::Zen => self.adjust_zen_speed(ZEN_SPEED_STEP),
+                    _ => {}
+                }
+            }
+            GameState::Paused => {
+                if keycode == KeyCode::M && self.menu_stack.current() == MenuState::Pause {
+                    self.menu_stack.push(MenuState::ConfirmQuit);
+                    return;
+                }
+                let menu = self.build_menu(self.menu_stack.current());
+                if let Some(event) = menu.key(self.menu_stack.frame_mut(), keycode) {
+                    self.apply_menu_event(event, gamepad);
+                }
+            }
+            GameState::GameOver => {
+                // R/M shortcuts only on the results screen so they can still be typed into a name
+                let shortcut = match keycode {
+                    KeyCode::R if self.menu_stack.current() == MenuState::GameOver => Some(MenuAction::Restart),
+                    KeyCode::M if self.menu_stack.current() == MenuState::GameOver => Some(MenuAction::MainMenu),
+                    _ => None,
+                };
+                if let Some(action) = shortcut {
+                    self.apply_menu_event(MenuEvent::Activate(action), gamepad);
+                    return;
+                }
+                let menu = self.build_menu(self.menu_stack.current());
+                if let Some(event) = menu.key(self.menu_stack.frame_mut(), keycode) {
+                    self.apply_menu_event(event, gamepad);
+                }
+            }
+        }
+    }
 }
 
 impl EventHandler for Game {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
         let dt = ctx.time.delta().as_secs_f32();
-        
+        self.poll_submission();
+
         match self.state {
+            GameState::Playing if self.resume_countdown > 0.0 => {
+                self.resume_countdown -= dt;
+                // Measure the next move from the end of the countdown
+                self.last_update = ctx.time.time_since_start().as_secs_f32();
+            }
             GameState::Playing => self.update_game(ctx, dt)?,
             GameState::Menu => {
                 // Update menu transitions if needed
@@ -653,49 +2256,39 @@ fn draw(&mut self, ctx: &mut Context) -> GameResult {
 
     match self.state {
         GameState::Menu => {
-            match self.menu_state {
-                MenuState::Main => self.draw_menu(ctx, &mut canvas)?,
-                MenuState::Difficulty => self.draw_difficulty_menu(ctx, &mut canvas)?,
-                MenuState::HighScores => self.draw_high_scores(ctx, &mut canvas)?,
-                MenuState::EnteringName => {
-                    let prompt_text = format!("Enter your name: {}_", self.player_name);
-                    let mut name_prompt = graphics::Text::new(prompt_text);
-                    // Store reference from set_scale
-                    let name_prompt = name_prompt.set_scale(32.0);
+            let menu = self.build_menu(self.menu_stack.current());
+            menu.draw(ctx, &mut canvas, self.menu_stack.frame_mut())?;
+        }
+        GameState::Playing => {
+            self.draw_game(ctx, &mut canvas)?;
 
-                    canvas.draw(
-                        name_prompt,  // Already a reference
-                        graphics::DrawParam::default()
-                            .dest(Point2 {
-                                x: (SCREEN_SIZE as f32 / 2.0) - 150.0,
-                                y: (SCREEN_SIZE as f32 / 2.0),
-                            })
-                            .color(graphics::Color::WHITE),
-                    );
-                }
+            if self.resume_countdown > 0.0 {
+                let mut countdown_text = graphics::Text::new(format!("{}", self.resume_countdown.ceil() as u32));
+                let countdown_text = countdown_text.set_scale(96.0);
+                let size = countdown_text.measure(ctx)?;
+                canvas.draw(
+                    countdown_text,
+                    graphics::DrawParam::default()
+                        .dest(Point2 {
+                            x: (SCREEN_SIZE as f32 - size.x) / 2.0,
+                            y: (SCREEN_SIZE as f32 - size.y) / 2.0,
+                        })
+                        .color(graphics::Color::WHITE),
+                );
             }
         }
-        GameState::Playing | GameState::Paused => self.draw_game(ctx, &mut canvas)?,
-        GameState::GameOver => {
+        GameState::Paused | GameState::GameOver => {
             self.draw_game(ctx, &mut canvas)?;
-            
-            let game_over_string = format!(
-                "Game Over!\nScore: {}\nPress R to restart\nPress M for menu",
-                self.score
-            );
-            let mut game_over_text = graphics::Text::new(game_over_string);
-            // Store reference from set_scale
-            let game_over_text = game_over_text.set_scale(32.0);
 
+            // Dim the board so the menu on top stands out
+            let overlay = graphics::Rect::new(0.0, 0.0, SCREEN_SIZE as f32, SCREEN_SIZE as f32);
             canvas.draw(
-                game_over_text,  // Already a reference
-                graphics::DrawParam::default()
-                    .dest(Point2 {
-                        x: (SCREEN_SIZE as f32 / 2.0) - 100.0,
-                        y: (SCREEN_SIZE as f32 / 2.0) - 60.0,
-                    })
-                    .color(graphics::Color::WHITE),
+                &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), overlay, OVERLAY_COLOR)?,
+                graphics::DrawParam::default(),
             );
+
+            let menu = self.build_menu(self.menu_stack.current());
+            menu.draw(ctx, &mut canvas, self.menu_stack.frame_mut())?;
         }
     }
 
@@ -705,148 +2298,119 @@ fn draw(&mut self, ctx: &mut Context) -> GameResult {
 
     fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
         if let Some(keycode) = input.keycode {
-            match self.state {
-                GameState::Menu => {
-                    match self.menu_state {
-                        MenuState::Main => {
-                            match keycode {
-                                KeyCode::Up => {
-                                    self.menu_selection = self.menu_selection.checked_sub(1).unwrap_or(3);
-                                }
-                                KeyCode::Down => {
-                                    self.menu_selection = (self.menu_selection + 1) % 4;
-                                }
-                                KeyCode::Return => {
-                                    match self.menu_selection {
-                                        0 => {
-                                            self.reset();
-                                            self.state = GameState::Playing;
-                                        }
-                                        1 => self.menu_state = MenuState::Difficulty,
-                                        2 => self.menu_state = MenuState::HighScores,
-                                        3 => std::process::exit(0),
-                                        _ => {}
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                        MenuState::Difficulty => {
-                            match keycode {
-                                KeyCode::Up => {
-                                    self.difficulty = match self.difficulty {
-                                        Difficulty::Easy => Difficulty::Expert,
-                                        Difficulty::Medium => Difficulty::Easy,
-                                        Difficulty::Hard => Difficulty::Medium,
-                                        Difficulty::Expert => Difficulty::Hard,
-                                    };
-                                    self.initial_cooldown = self.difficulty.get_info().speed;
-                                }
-                                KeyCode::Down => {
-                                    self.difficulty = match self.difficulty {
-                                        Difficulty::Easy => Difficulty::Medium,
-                                        Difficulty::Medium => Difficulty::Hard,
-                                        Difficulty::Hard => Difficulty::Expert,
-                                        Difficulty::Expert => Difficulty::Easy,
-                                    };
-                                    self.initial_cooldown = self.difficulty.get_info().speed;
-                                }
-                                KeyCode::Escape => self.menu_state = MenuState::Main,
-                                _ => {}
-                            }
-                        }
-                        MenuState::HighScores => {
-                            if keycode == KeyCode::Escape {
-                                self.menu_state = MenuState::Main;
-                            }
-                        }
-                        MenuState::EnteringName => {
-                            match keycode {
-                                KeyCode::Return => {
-                                    if !self.player_name.is_empty() {
-                                        self.add_high_score(self.score);
-                                        self.menu_state = MenuState::HighScores;
-                                        self.name_input_active = false;
-                                    }
-                                }
-                                KeyCode::Back => {
-                                    self.player_name.pop();
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-                GameState::Playing => {
-                    match keycode {
-                        KeyCode::Up if self.direction != Direction::Down => {
-                            self.next_direction = Direction::Up;
-                        }
-                        KeyCode::Down if self.direction != Direction::Up => {
-                            self.next_direction = Direction::Down;
-                        }
-                        KeyCode::Left if self.direction != Direction::Right => {
-                            self.next_direction = Direction::Left;
-                        }
-                        KeyCode::Right if self.direction != Direction::Left => {
-                            self.next_direction = Direction::Right;
-                        }
-                        KeyCode::Escape => {
-                            self.state = GameState::Paused;
-                        }
-                        _ => {}
-                    }
-                }
-                GameState::Paused => {
-                    match keycode {
-                        KeyCode::Escape => {
-                            self.state = GameState::Playing;
-                        }
-                        KeyCode::M => {
-                            self.state = GameState::Menu;
-                        }
-                        _ => {}
-                    }
-                }
-                GameState::GameOver => {
-                    match keycode {
-                        KeyCode::R => {
-                            if !self.name_input_active {
-                                self.add_high_score(self.score);
-                            } else {
-                                self.reset();
-                                self.state = GameState::Playing;
-                            }
-                        }
-                        KeyCode::M => {
-                            self.state = GameState::Menu;
-                        }
-                        _ => {}
-                    }
-                }
+            self.handle_key(keycode, None);
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) -> GameResult {
+        if let Some(keycode) = self.gamepad_button_to_key(btn) {
+            self.handle_gamepad_key(keycode, id);
+        }
+        Ok(())
+    }
+
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) -> GameResult {
+        let keycode = match axis {
+            Axis::LeftStickX if value > STICK_DEADZONE => KeyCode::Right,
+            Axis::LeftStickX if value < -STICK_DEADZONE => KeyCode::Left,
+            Axis::LeftStickY if value > STICK_DEADZONE => KeyCode::Up,
+            Axis::LeftStickY if value < -STICK_DEADZONE => KeyCode::Down,
+            Axis::LeftStickX | Axis::LeftStickY => {
+                // Stick back near the centre, so the next push counts as a new input
+                self.held_stick_axes.retain(|&held| held != (id, axis));
+                return Ok(());
             }
+            _ => return Ok(()),
+        };
+
+        // Treat the stick like a button: one input per push past the deadzone
+        if !self.held_stick_axes.contains(&(id, axis)) {
+            self.held_stick_axes.push((id, axis));
+            self.handle_gamepad_key(keycode, id);
+        }
+        Ok(())
+    }
+
+    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) -> GameResult {
+        if self.state != GameState::Playing {
+            let menu = self.build_menu(self.menu_stack.current());
+            menu.mouse_motion(self.menu_stack.frame_mut(), x, y);
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> GameResult {
+        if self.state == GameState::Playing || button != MouseButton::Left {
+            return Ok(());
+        }
+
+        let menu = self.build_menu(self.menu_stack.current());
+        if let Some(event) = menu.click(self.menu_stack.frame_mut(), x, y) {
+            self.apply_menu_event(event, None);
+        }
+        Ok(())
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) -> GameResult {
+        if self.state != GameState::Playing {
+            let menu = self.build_menu(self.menu_stack.current());
+            menu.scroll(self.menu_stack.frame_mut(), -y.signum() as i32 * 3);
+        }
+        Ok(())
+    }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) -> GameResult {
+        if !gained && self.state == GameState::Playing {
+            self.pause();
         }
         Ok(())
     }
 
     fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
-        if self.name_input_active && self.player_name.len() < 8 && character.is_alphanumeric() {
-            self.player_name.push(character);
+        if self.state != GameState::Playing {
+            let menu = self.build_menu(self.menu_stack.current());
+            if let Some(event) = menu.text_input(self.menu_stack.frame_mut(), character) {
+                self.apply_menu_event(event, None);
+            }
         }
         Ok(())
     }
 }
-// Add Direction enum that was missing
-#[derive(PartialEq, Clone, Copy)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
 fn main() -> GameResult {
-    let resource_dir = std::path::PathBuf::from("./resources");
+    let mut data_dir_override = None;
+    let mut score_command = None;
+    let mut leaderboard_url = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--data-dir", Some(dir)) => data_dir_override = Some(PathBuf::from(dir)),
+            ("--export-scores", Some(file)) => score_command = Some(ScoreCommand::Export(PathBuf::from(file))),
+            ("--import-scores", Some(file)) => score_command = Some(ScoreCommand::Import(PathBuf::from(file))),
+            ("--leaderboard", Some(url)) => leaderboard_url = Some(url),
+            _ => {
+                eprintln!(
+                    "Unexpected argument: {}\nUsage: snake_game [--data-dir <path>] [--leaderboard <url>] [--export-scores <file> | --import-scores <file>]",
+                    arg
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let data_dir = storage::data_dir(data_dir_override)?;
+    if let Some(command) = score_command {
+        match run_score_command(command, &data_dir) {
+            Ok(summary) => println!("{}", summary),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    let resource_dir = storage::resource_dir();
     let window_setup = ggez::conf::WindowSetup::default()
         .title("Snake Game")
         .vsync(true);
@@ -860,6 +2424,6 @@ fn main() -> GameResult {
         .window_mode(window_mode)
         .build()?;
 
-    let game = Game::new(&mut ctx)?;
+    let game = Game::new(&mut ctx, data_dir, leaderboard_url)?;
     event::run(ctx, event_loop, game)
 }
\ No newline at end of file
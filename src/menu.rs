@@ -0,0 +1,561 @@
+// Small declarative menu framework: screens describe their items as data with `Menu`,
+// and all keyboard/mouse navigation and layout lives here instead of in each screen.
+use ggez::input::keyboard::KeyCode;
+use ggez::mint::Point2;
+use ggez::{graphics, Context, GameResult};
+
+const TITLE_SCALE: f32 = 40.0;
+const TITLE_Y: f32 = 40.0;
+const NOTICE_Y: f32 = 85.0;
+const CONTENT_TOP: f32 = 120.0;
+const FOOTER_MARGIN: f32 = 50.0;
+const LEFT_MARGIN: f32 = 50.0;
+const SLIDER_SEGMENTS: usize = 10;
+const LIST_SCALE: f32 = 20.0;
+const LIST_LINE_HEIGHT: f32 = 25.0;
+// The window is square, so one dimension covers both axes
+const SCREEN: f32 = crate::SCREEN_SIZE as f32;
+
+#[derive(Clone, Debug)]
+pub enum Widget {
+    Button,
+    Toggle(bool),
+    Slider { value: f32, min: f32, max: f32, step: f32 },
+    // One of a fixed set of options, cycled with Left/Right like tabs
+    Choice { options: Vec<String>, index: usize },
+    TextField { value: String, max_len: usize },
+    Heading,
+    Label,
+}
+
+impl Widget {
+    fn selectable(&self) -> bool {
+        !matches!(self, Widget::Heading | Widget::Label)
+    }
+
+    fn scale(&self) -> f32 {
+        match self {
+            Widget::Heading | Widget::Choice { .. } => 24.0,
+            Widget::Label => 20.0,
+            _ => 32.0,
+        }
+    }
+
+    fn line_height(&self) -> f32 {
+        match self {
+            Widget::Heading => 32.0,
+            Widget::Choice { .. } => 34.0,
+            Widget::Label => 25.0,
+            _ => 50.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MenuItem<A> {
+    pub label: String,
+    pub widget: Widget,
+    pub action: Option<A>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align {
+    Center,
+    Left,
+}
+
+// What the game should do in response to an input on a menu
+#[derive(Clone, PartialEq, Debug)]
+pub enum MenuEvent<A> {
+    Activate(A),
+    Toggle(A, bool),
+    Slide(A, f32),
+    Choose(A, usize),
+    Text(A, String),
+    Back,
+}
+
+pub struct Menu<A> {
+    title: String,
+    items: Vec<MenuItem<A>>,
+    footer: Option<String>,
+    notice: Option<String>,
+    align: Align,
+    // Rows drawn under the items in a window that scrolls instead of squashing
+    list: Option<Vec<String>>,
+}
+
+impl<A: Copy> Menu<A> {
+    pub fn new(title: impl Into<String>) -> Self {
+        Menu {
+            title: title.into(),
+            items: Vec::new(),
+            footer: None,
+            notice: None,
+            align: Align::Center,
+            list: None,
+        }
+    }
+
+    fn item(mut self, label: impl Into<String>, widget: Widget, action: Option<A>) -> Self {
+        self.items.push(MenuItem {
+            label: label.into(),
+            widget,
+            action,
+        });
+        self
+    }
+
+    pub fn button(self, label: impl Into<String>, action: A) -> Self {
+        self.item(label, Widget::Button, Some(action))
+    }
+
+    pub fn toggle(self, label: impl Into<String>, value: bool, action: A) -> Self {
+        self.item(label, Widget::Toggle(value), Some(action))
+    }
+
+    pub fn slider(self, label: impl Into<String>, value: f32, min: f32, max: f32, step: f32, action: A) -> Self {
+        self.item(label, Widget::Slider { value, min, max, step }, Some(action))
+    }
+
+    pub fn choice(self, label: impl Into<String>, options: Vec<String>, index: usize, action: A) -> Self {
+        self.item(label, Widget::Choice { options, index }, Some(action))
+    }
+
+    pub fn text_field(self, label: impl Into<String>, value: &str, max_len: usize, action: A) -> Self {
+        let value = value.to_string();
+        self.item(label, Widget::TextField { value, max_len }, Some(action))
+    }
+
+    pub fn heading(self, text: impl Into<String>) -> Self {
+        self.item(text, Widget::Heading, None)
+    }
+
+    pub fn label(self, text: impl Into<String>) -> Self {
+        self.item(text, Widget::Label, None)
+    }
+
+    // Clickable hint at the bottom of the screen that acts like Escape
+    pub fn footer(mut self, text: impl Into<String>) -> Self {
+        self.footer = Some(text.into());
+        self
+    }
+
+    // Warning shown in red under the title, e.g. when a save file couldn't be read
+    pub fn notice(mut self, text: Option<String>) -> Self {
+        self.notice = text;
+        self
+    }
+
+    pub fn align_left(mut self) -> Self {
+        self.align = Align::Left;
+        self
+    }
+
+    // Long content such as score tables; scrolled with the mouse wheel, Page Up/Down,
+    // or by pressing Up/Down past the last item
+    pub fn scroll_list(mut self, rows: Vec<String>, empty_text: impl Into<String>) -> Self {
+        self.list = Some(if rows.is_empty() { vec![empty_text.into()] } else { rows });
+        self
+    }
+
+    pub fn scroll(&self, frame: &mut MenuFrame<impl Copy>, rows: i32) {
+        let Some(list) = &self.list else {
+            return;
+        };
+        let max_scroll = list.len().saturating_sub(frame.visible_rows.max(1));
+        frame.scroll = frame.scroll.saturating_add_signed(rows as isize).min(max_scroll);
+    }
+
+    fn can_scroll(&self, frame: &MenuFrame<impl Copy>, forward: bool) -> bool {
+        match &self.list {
+            Some(list) if forward => frame.scroll + frame.visible_rows < list.len(),
+            Some(_) => frame.scroll > 0,
+            None => false,
+        }
+    }
+
+    fn selectable_indices(&self) -> Vec<usize> {
+        (0..self.items.len())
+            .filter(|&i| self.items[i].widget.selectable())
+            .collect()
+    }
+
+    // Keeps the stored selection on a selectable item when the menu contents change
+    fn clamp_selection(&self, frame: &mut MenuFrame<impl Copy>) {
+        let selectable = self.selectable_indices();
+        if !selectable.contains(&frame.selected) {
+            frame.selected = selectable.first().copied().unwrap_or(0);
+        }
+    }
+
+    fn move_selection(&self, frame: &mut MenuFrame<impl Copy>, forward: bool) {
+        let selectable = self.selectable_indices();
+        if selectable.is_empty() {
+            return;
+        }
+        let current = selectable.iter().position(|&i| i == frame.selected).unwrap_or(0);
+        // The list behaves like an extension below the last item
+        if current == selectable.len() - 1 && self.can_scroll(frame, forward) {
+            self.scroll(frame, if forward { 1 } else { -1 });
+            return;
+        }
+        let next = if forward {
+            (current + 1) % selectable.len()
+        } else {
+            current.checked_sub(1).unwrap_or(selectable.len() - 1)
+        };
+        frame.selected = selectable[next];
+    }
+
+    fn activate(&self, index: usize) -> Option<MenuEvent<A>> {
+        let item = self.items.get(index)?;
+        let action = item.action?;
+        match &item.widget {
+            Widget::Toggle(value) => Some(MenuEvent::Toggle(action, !value)),
+            Widget::Button | Widget::TextField { .. } => Some(MenuEvent::Activate(action)),
+            Widget::Choice { .. } => self.adjust(index, true),
+            _ => None,
+        }
+    }
+
+    fn adjust(&self, index: usize, increase: bool) -> Option<MenuEvent<A>> {
+        let item = self.items.get(index)?;
+        let action = item.action?;
+        match item.widget {
+            Widget::Slider { value, min, max, step } => {
+                let delta = if increase { step } else { -step };
+                Some(MenuEvent::Slide(action, (value + delta).clamp(min, max)))
+            }
+            Widget::Toggle(value) => Some(MenuEvent::Toggle(action, !value)),
+            Widget::Choice { ref options, index } => {
+                let next = if increase { index + 1 } else { index + options.len() - 1 };
+                Some(MenuEvent::Choose(action, next % options.len()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn key(&self, frame: &mut MenuFrame<impl Copy>, keycode: KeyCode) -> Option<MenuEvent<A>> {
+        self.clamp_selection(frame);
+        // The keyboard takes over from the mouse until it moves again
+        frame.hovered = None;
+        match keycode {
+            KeyCode::Up => {
+                self.move_selection(frame, false);
+                None
+            }
+            KeyCode::Down | KeyCode::Tab => {
+                self.move_selection(frame, true);
+                None
+            }
+            KeyCode::PageUp | KeyCode::PageDown => {
+                let page = frame.visible_rows.max(1) as i32;
+                self.scroll(frame, if keycode == KeyCode::PageUp { -page } else { page });
+                None
+            }
+            KeyCode::Left => self.adjust(frame.selected, false),
+            KeyCode::Right => self.adjust(frame.selected, true),
+            KeyCode::Return => self.activate(frame.selected),
+            KeyCode::Back => {
+                let item = self.items.get(frame.selected)?;
+                match (&item.widget, item.action) {
+                    (Widget::TextField { value, .. }, Some(action)) => {
+                        let mut value = value.clone();
+                        value.pop();
+                        Some(MenuEvent::Text(action, value))
+                    }
+                    _ => None,
+                }
+            }
+            KeyCode::Escape => Some(MenuEvent::Back),
+            _ => None,
+        }
+    }
+
+    pub fn text_input(&self, frame: &mut MenuFrame<impl Copy>, character: char) -> Option<MenuEvent<A>> {
+        self.clamp_selection(frame);
+        let item = self.items.get(frame.selected)?;
+        match (&item.widget, item.action) {
+            (Widget::TextField { value, max_len }, Some(action))
+                if value.chars().count() < *max_len && character.is_alphanumeric() =>
+            {
+                let mut value = value.clone();
+                value.push(character);
+                Some(MenuEvent::Text(action, value))
+            }
+            _ => None,
+        }
+    }
+
+    // Hovering moves the selection, except away from a text field: typing keeps going to
+    // the field and the hovered item is only highlighted
+    pub fn mouse_motion(&self, frame: &mut MenuFrame<impl Copy>, x: f32, y: f32) {
+        self.clamp_selection(frame);
+        frame.hovered = frame.hit_test(x, y);
+        let editing = matches!(self.items.get(frame.selected).map(|item| &item.widget), Some(Widget::TextField { .. }));
+        if let (Some(Hit::Item(i)), false) = (frame.hovered, editing) {
+            frame.selected = i;
+        }
+    }
+
+    pub fn click(&self, frame: &mut MenuFrame<impl Copy>, x: f32, y: f32) -> Option<MenuEvent<A>> {
+        let hit = frame.hit_test(x, y)?;
+        frame.hovered = None;
+        match hit {
+            Hit::Back => Some(MenuEvent::Back),
+            Hit::Item(i) => {
+                frame.selected = i;
+                match self.items[i].widget {
+                    // Clicking the left half of a slider lowers it, the right half raises it
+                    Widget::Slider { .. } | Widget::Choice { .. } => {
+                        let (rect, _) = frame.hitboxes.iter().find(|(_, hit)| *hit == Hit::Item(i))?;
+                        self.adjust(i, x > rect.x + rect.w / 2.0)
+                    }
+                    // Clicking a text field only focuses it; Return or a button submits it
+                    Widget::TextField { .. } => None,
+                    _ => self.activate(i),
+                }
+            }
+        }
+    }
+
+    fn item_text(&self, index: usize, selected: bool) -> String {
+        let item = &self.items[index];
+        match &item.widget {
+            Widget::Button | Widget::Heading | Widget::Label => item.label.clone(),
+            Widget::Toggle(value) => format!("{}: {}", item.label, if *value { "On" } else { "Off" }),
+            Widget::Slider { value, min, max, .. } => {
+                let filled = (((value - min) / (max - min)) * SLIDER_SEGMENTS as f32).round() as usize;
+                format!(
+                    "{}: <{}{}>",
+                    item.label,
+                    "#".repeat(filled),
+                    "-".repeat(SLIDER_SEGMENTS - filled.min(SLIDER_SEGMENTS)),
+                )
+            }
+            Widget::Choice { options, index } => format!("{}: < {} >", item.label, options[*index]),
+            Widget::TextField { value, .. } => {
+                format!("{}: {}{}", item.label, value, if selected { "_" } else { "" })
+            }
+        }
+    }
+
+    fn x_for(&self, width: f32) -> f32 {
+        match self.align {
+            Align::Center => (SCREEN - width) / 2.0,
+            Align::Left => LEFT_MARGIN,
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, canvas: &mut graphics::Canvas, frame: &mut MenuFrame<impl Copy>) -> GameResult {
+        self.clamp_selection(frame);
+        frame.hitboxes.clear();
+
+        let mut title_text = graphics::Text::new(self.title.as_str());
+        let title = title_text.set_scale(TITLE_SCALE);
+        let title_width = title.measure(ctx)?.x;
+        canvas.draw(
+            title,
+            graphics::DrawParam::default()
+                .dest(Point2 {
+                    x: (SCREEN - title_width) / 2.0,
+                    y: TITLE_Y,
+                })
+                .color(graphics::Color::WHITE),
+        );
+
+        if let Some(notice) = &self.notice {
+            let mut notice_text = graphics::Text::new(notice.as_str());
+            let text = notice_text
+                .set_scale(18.0)
+                .set_bounds(Point2 { x: SCREEN - 2.0 * LEFT_MARGIN, y: CONTENT_TOP - NOTICE_Y })
+                .set_layout(graphics::TextLayout {
+                    h_align: graphics::TextAlign::Middle,
+                    v_align: graphics::TextAlign::Begin,
+                });
+            canvas.draw(
+                text,
+                graphics::DrawParam::default()
+                    .dest(Point2 { x: SCREEN / 2.0, y: NOTICE_Y })
+                    .color(graphics::Color::RED),
+            );
+        }
+
+        // Squash the spacing when a long menu wouldn't otherwise fit above the footer
+        let bottom = SCREEN - FOOTER_MARGIN - 10.0;
+        let available = bottom - CONTENT_TOP;
+        let total: f32 = self.items.iter().map(|item| item.widget.line_height()).sum();
+        let squash = if total > available { available / total } else { 1.0 };
+
+        let mut y = CONTENT_TOP;
+        for (i, item) in self.items.iter().enumerate() {
+            let selected = item.widget.selectable() && i == frame.selected;
+            let hovered = frame.hovered == Some(Hit::Item(i));
+            let color = match item.widget {
+                Widget::Heading => graphics::Color::YELLOW,
+                Widget::Label => graphics::Color::WHITE,
+                _ if selected || hovered => graphics::Color::GREEN,
+                _ => graphics::Color::WHITE,
+            };
+
+            let mut item_text = graphics::Text::new(self.item_text(i, selected));
+            let text = item_text.set_scale(item.widget.scale() * squash.max(0.8));
+            let size = text.measure(ctx)?;
+            let dest = Point2 { x: self.x_for(size.x), y };
+            if item.widget.selectable() {
+                frame.hitboxes.push((graphics::Rect::new(dest.x, dest.y, size.x, size.y), Hit::Item(i)));
+            }
+
+            canvas.draw(text, graphics::DrawParam::default().dest(dest).color(color));
+            y += item.widget.line_height() * squash;
+        }
+
+        if let Some(list) = &self.list {
+            self.draw_list(ctx, canvas, frame, list, y, bottom)?;
+        }
+
+        if let Some(footer) = &self.footer {
+            let mut footer_text = graphics::Text::new(footer.as_str());
+            let text = footer_text.set_scale(20.0);
+            let size = text.measure(ctx)?;
+            let dest = Point2 {
+                x: (SCREEN - size.x) / 2.0,
+                y: SCREEN - FOOTER_MARGIN,
+            };
+            frame.hitboxes.push((graphics::Rect::new(dest.x, dest.y, size.x, size.y), Hit::Back));
+            let color = if frame.hovered == Some(Hit::Back) {
+                graphics::Color::WHITE
+            } else {
+                graphics::Color::YELLOW
+            };
+            canvas.draw(text, graphics::DrawParam::default().dest(dest).color(color));
+        }
+
+        Ok(())
+    }
+
+    fn draw_list(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        frame: &mut MenuFrame<impl Copy>,
+        list: &[String],
+        top: f32,
+        bottom: f32,
+    ) -> GameResult {
+        // One line at each end is kept for the "more" markers
+        frame.visible_rows = (((bottom - top) / LIST_LINE_HEIGHT) as usize).saturating_sub(2).max(1);
+        frame.scroll = frame.scroll.min(list.len().saturating_sub(frame.visible_rows));
+        let end = (frame.scroll + frame.visible_rows).min(list.len());
+
+        let above = (frame.scroll > 0).then(|| format!("^ {} more", frame.scroll));
+        let below = (end < list.len()).then(|| format!("v {} more", list.len() - end));
+        let lines = std::iter::once((above, graphics::Color::YELLOW))
+            .chain(list[frame.scroll..end].iter().map(|row| (Some(row.clone()), graphics::Color::WHITE)))
+            .chain(std::iter::once((below, graphics::Color::YELLOW)));
+
+        let mut y = top;
+        for (line, color) in lines {
+            if let Some(line) = line {
+                let mut line_text = graphics::Text::new(line);
+                let text = line_text.set_scale(LIST_SCALE);
+                let size = text.measure(ctx)?;
+                canvas.draw(text, graphics::DrawParam::default().dest(Point2 { x: self.x_for(size.x), y }).color(color));
+            }
+            y += LIST_LINE_HEIGHT;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Hit {
+    Item(usize),
+    Back,
+}
+
+// Per-screen navigation state, kept on the stack so returning to a screen restores its cursor
+pub struct MenuFrame<S> {
+    pub screen: S,
+    selected: usize,
+    hovered: Option<Hit>,
+    hitboxes: Vec<(graphics::Rect, Hit)>,
+    // First list row shown, and how many fit on screen as of the last draw
+    scroll: usize,
+    visible_rows: usize,
+}
+
+impl<S: Copy> MenuFrame<S> {
+    fn new(screen: S) -> Self {
+        MenuFrame {
+            screen,
+            selected: 0,
+            hovered: None,
+            hitboxes: Vec::new(),
+            scroll: 0,
+            visible_rows: 0,
+        }
+    }
+
+    fn hit_test(&self, x: f32, y: f32) -> Option<Hit> {
+        self.hitboxes
+            .iter()
+            .find(|(rect, _)| rect.contains(Point2 { x, y }))
+            .map(|(_, hit)| *hit)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index;
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.scroll = 0;
+    }
+}
+
+// Back stack of screens; the root screen is never popped
+pub struct MenuStack<S> {
+    frames: Vec<MenuFrame<S>>,
+}
+
+impl<S: Copy> MenuStack<S> {
+    pub fn new(root: S) -> Self {
+        MenuStack {
+            frames: vec![MenuFrame::new(root)],
+        }
+    }
+
+    pub fn current(&self) -> S {
+        self.frames.last().unwrap().screen
+    }
+
+    pub fn frame_mut(&mut self) -> &mut MenuFrame<S> {
+        self.frames.last_mut().unwrap()
+    }
+
+    pub fn push(&mut self, screen: S) {
+        self.frames.push(MenuFrame::new(screen));
+    }
+
+    pub fn pop(&mut self) -> bool {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Swaps the current screen without growing the stack, e.g. after submitting a form
+    pub fn replace(&mut self, screen: S) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+        self.push(screen);
+    }
+
+    pub fn reset(&mut self) {
+        self.frames.truncate(1);
+    }
+}
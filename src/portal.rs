@@ -0,0 +1,35 @@
+// Paired teleporters: moving into one end brings the head out of the other
+use crate::Position;
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+// Random portals in classic runs move somewhere new after this much food
+pub const RELOCATE_AFTER_FOOD: u32 = 5;
+pub const PORTAL_COLORS: [Color; 2] = [
+    Color::new(0.0, 0.9, 1.0, 1.0),  // Cyan
+    Color::new(1.0, 0.55, 0.0, 1.0), // Orange
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PortalPair {
+    pub ends: [Position; 2],
+}
+
+impl PortalPair {
+    pub fn contains(&self, pos: Position) -> bool {
+        self.ends.contains(&pos)
+    }
+
+    pub fn partner(&self, pos: Position) -> Option<Position> {
+        match self.ends {
+            [a, b] if a == pos => Some(b),
+            [a, b] if b == pos => Some(a),
+            _ => None,
+        }
+    }
+}
+
+// Where a head stepping onto `pos` actually ends up
+pub fn exit_for(portals: &[PortalPair], pos: Position) -> Option<Position> {
+    portals.iter().find_map(|pair| pair.partner(pos))
+}
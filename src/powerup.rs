@@ -0,0 +1,116 @@
+// Special pickups that sometimes appear when food is eaten, and the timed effects they give
+use crate::expiring::Expiring;
+use crate::Position;
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    SlowMotion,
+    ScoreDoubler,
+    Ghost,
+    Shrink,
+    Magnet,
+}
+
+pub struct PowerUpInfo {
+    pub label: &'static str,
+    // Chance of appearing each time food is eaten
+    pub spawn_chance: f64,
+    // Moves it stays on the board before vanishing
+    pub lifetime: u32,
+    // Moves the effect lasts once collected; zero for instant effects
+    pub duration: u32,
+    pub color: Color,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::SlowMotion,
+        PowerUpKind::ScoreDoubler,
+        PowerUpKind::Ghost,
+        PowerUpKind::Shrink,
+        PowerUpKind::Magnet,
+    ];
+
+    pub fn info(&self) -> PowerUpInfo {
+        match self {
+            PowerUpKind::SlowMotion => PowerUpInfo {
+                label: "Slow-mo",
+                spawn_chance: 0.08,
+                lifetime: 55,
+                duration: 40,
+                color: Color::new(0.3, 0.6, 1.0, 1.0),
+            },
+            PowerUpKind::ScoreDoubler => PowerUpInfo {
+                label: "x2 Score",
+                spawn_chance: 0.08,
+                lifetime: 55,
+                duration: 65,
+                color: Color::new(1.0, 0.85, 0.0, 1.0),
+            },
+            PowerUpKind::Ghost => PowerUpInfo {
+                label: "Ghost",
+                spawn_chance: 0.05,
+                lifetime: 40,
+                duration: 40,
+                color: Color::new(0.85, 0.85, 1.0, 1.0),
+            },
+            PowerUpKind::Shrink => PowerUpInfo {
+                label: "Shrink",
+                spawn_chance: 0.06,
+                lifetime: 55,
+                duration: 0,
+                color: Color::new(1.0, 0.4, 0.8, 1.0),
+            },
+            PowerUpKind::Magnet => PowerUpInfo {
+                label: "Magnet",
+                spawn_chance: 0.05,
+                lifetime: 40,
+                duration: 55,
+                color: Color::new(0.7, 0.3, 1.0, 1.0),
+            },
+        }
+    }
+}
+
+// A pickup waiting on the board
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub position: Position,
+    #[serde(default)]
+    pub ticks_left: u32,
+}
+
+// A collected pickup that's still running
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    #[serde(default)]
+    pub ticks_left: u32,
+}
+
+impl Expiring for PowerUp {
+    fn ticks_left(&mut self) -> Option<&mut u32> {
+        Some(&mut self.ticks_left)
+    }
+}
+
+impl Expiring for ActiveEffect {
+    fn ticks_left(&mut self) -> Option<&mut u32> {
+        Some(&mut self.ticks_left)
+    }
+}
+
+// Collecting an effect that's already running restarts its timer rather than stacking
+pub fn activate(effects: &mut Vec<ActiveEffect>, kind: PowerUpKind) {
+    let duration = kind.info().duration;
+    if duration == 0 {
+        return;
+    }
+    match effects.iter_mut().find(|effect| effect.kind == kind) {
+        Some(effect) => effect.ticks_left = duration,
+        None => effects.push(ActiveEffect { kind, ticks_left: duration }),
+    }
+}
@@ -0,0 +1,137 @@
+// Player profiles and the lifetime stats tracked for each of them
+use snake_shared::storage;
+use snake_shared::scores::{Difficulty, GameMode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+const RECENT_SCORES: usize = 10;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum DeathCause {
+    Wall,
+    SelfCollision,
+    Bomb,
+    Obstacle,
+    // Not a death as such: a Time Attack run's clock ran down
+    TimeUp,
+}
+
+impl DeathCause {
+    pub const ALL: [DeathCause; 5] = [
+        DeathCause::Wall,
+        DeathCause::SelfCollision,
+        DeathCause::Bomb,
+        DeathCause::Obstacle,
+        DeathCause::TimeUp,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "Hit a wall",
+            DeathCause::SelfCollision => "Ran into self",
+            DeathCause::Bomb => "Ate a bomb",
+            DeathCause::Obstacle => "Hit a moving block",
+            DeathCause::TimeUp => "Ran out of time",
+        }
+    }
+}
+
+// Summary of a finished run, folded into the active profile's totals
+pub struct RunStats {
+    pub score: u32,
+    pub difficulty: Difficulty,
+    pub mode: GameMode,
+    pub food_eaten: u32,
+    pub snake_length: usize,
+    pub time_played: f32,
+    pub death_cause: DeathCause,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    pub games_played: u32,
+    pub food_eaten: u32,
+    pub longest_snake: usize,
+    // Seconds spent in play, not counting menus or pauses
+    pub time_played: f32,
+    // Classic runs only, so a short Time Attack run isn't weighed against them
+    pub best_scores: HashMap<Difficulty, u32>,
+    // Bests for the other modes, by mode and then difficulty
+    #[serde(default)]
+    pub mode_bests: HashMap<GameMode, HashMap<Difficulty, u32>>,
+    pub deaths: HashMap<DeathCause, u32>,
+    // Latest scores, oldest first, for Adaptive runs to start from
+    #[serde(default)]
+    pub recent_scores: Vec<u32>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Profile {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, run: &RunStats) {
+        self.games_played += 1;
+        self.food_eaten += run.food_eaten;
+        self.longest_snake = self.longest_snake.max(run.snake_length);
+        self.time_played += run.time_played;
+        let bests = match run.mode {
+            GameMode::Classic => &mut self.best_scores,
+            mode => self.mode_bests.entry(mode).or_default(),
+        };
+        let best = bests.entry(run.difficulty).or_insert(0);
+        *best = (*best).max(run.score);
+        *self.deaths.entry(run.death_cause).or_insert(0) += 1;
+        self.recent_scores.push(run.score);
+        if self.recent_scores.len() > RECENT_SCORES {
+            self.recent_scores.remove(0);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Profiles {
+    pub active: Option<usize>,
+    pub profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        storage::load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        storage::write_atomic(path, &json)
+    }
+
+    pub fn active(&self) -> Option<&Profile> {
+        self.profiles.get(self.active?)
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Profile> {
+        self.profiles.get_mut(self.active?)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.profiles.len() {
+            self.active = Some(index);
+        }
+    }
+
+    // Switches to the profile with this name, creating it if it doesn't exist yet
+    pub fn create(&mut self, name: &str) {
+        match self.profiles.iter().position(|profile| profile.name == name) {
+            Some(index) => self.active = Some(index),
+            None => {
+                self.profiles.push(Profile::new(name));
+                self.active = Some(self.profiles.len() - 1);
+            }
+        }
+    }
+}